@@ -0,0 +1,160 @@
+//! Session fingerprint, used in report caching.
+//! The fingerprint is made of one digest per component: every option
+//! that affects the output, and every input file (size and modification time).
+//! Comparing components one by one allows to explain why a report is regenerated.
+use std::{
+    collections::hash_map::DefaultHasher,
+    collections::BTreeMap,
+    fs::{metadata, read_to_string, write},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use clap::ArgMatches;
+
+use crate::preprocessing::PreprocessingPipeline;
+
+/// Options that do not affect the output products.
+/// Input selection options are not needed either: input files are fingerprinted.
+const IGNORED_OPTIONS: [&str; 18] = [
+    "filepath",
+    "directory",
+    "list",
+    "include",
+    "exclude",
+    "depth",
+    "start",
+    "end",
+    "station",
+    "jobs",
+    "quiet",
+    "workspace",
+    "session",
+    "pipeline",
+    "terrain-mask",
+    "summary",
+    "report-force",
+    "explain-cache",
+];
+
+/// Session fingerprint
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Fingerprint {
+    /// Digest of each component
+    components: BTreeMap<String, String>,
+}
+
+/// Hashes anything to hexadecimal digest
+fn digest<T: Hash>(value: T) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+impl Fingerprint {
+    /// Inserts a new component
+    fn insert<T: Hash>(&mut self, component: String, value: T) {
+        self.components.insert(component, digest(value));
+    }
+
+    /// Inserts all options of these [ArgMatches], prefixed by `prefix`
+    fn insert_options(&mut self, prefix: &str, matches: &ArgMatches) {
+        for id in matches.ids() {
+            let id = id.as_str();
+
+            if IGNORED_OPTIONS.contains(&id) {
+                continue;
+            }
+
+            // ids may also describe groups, that do not have values
+            if let Ok(Some(values)) = matches.try_get_raw(id) {
+                let values = values
+                    .map(|value| value.to_string_lossy().to_string())
+                    .collect::<Vec<_>>();
+
+                self.insert(format!("option:{}{}", prefix, id), values);
+            }
+        }
+    }
+
+    /// Builds the [Fingerprint] of this session
+    pub fn new(
+        matches: &ArgMatches,
+        session: Option<&String>,
+        preprocessing: &PreprocessingPipeline,
+        inputs: &[PathBuf],
+    ) -> Self {
+        let mut fingerprint = Self::default();
+
+        // report layout may change between versions
+        fingerprint.insert("version".to_string(), env!("CARGO_PKG_VERSION"));
+
+        fingerprint.insert_options("", matches);
+
+        if let Some((opmode, submatches)) = matches.subcommand() {
+            fingerprint.insert("opmode".to_string(), opmode);
+            fingerprint.insert_options(&format!("{}:", opmode), submatches);
+        }
+
+        // the session file path does not matter, its content does
+        if let Some(session) = session {
+            fingerprint.insert("session".to_string(), session);
+        }
+
+        // replayed pipelines and terrain masks are not described by the command line
+        if let Ok(pipeline) = serde_json::to_string(preprocessing) {
+            fingerprint.insert("preprocessing".to_string(), pipeline);
+        }
+
+        for input in inputs {
+            let stamp = metadata(input).ok().map(|meta| {
+                let mtime = meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|t| t.as_nanos());
+                (meta.len(), mtime)
+            });
+
+            fingerprint.insert(format!("input:{}", input.display()), stamp);
+        }
+
+        fingerprint
+    }
+
+    /// Parses a [Fingerprint] previously stored with [Self::store]
+    pub fn from_file(path: &Path) -> Option<Self> {
+        let content = read_to_string(path).ok()?;
+        let components = serde_json::from_str::<BTreeMap<String, String>>(&content).ok()?;
+        Some(Self { components })
+    }
+
+    /// Stores this [Fingerprint]
+    pub fn store(&self, path: &Path) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(&self.components)?;
+        write(path, content)
+    }
+
+    /// Returns the list of differences with a previous [Fingerprint].
+    /// Identical fingerprints return an empty list.
+    pub fn differences(&self, previous: &Self) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        for (component, value) in self.components.iter() {
+            match previous.components.get(component) {
+                Some(prev) if prev == value => {},
+                Some(_) => differences.push(format!("{} changed", component)),
+                None => differences.push(format!("{} is new", component)),
+            }
+        }
+
+        for component in previous.components.keys() {
+            if !self.components.contains_key(component) {
+                differences.push(format!("{} was removed", component));
+            }
+        }
+
+        differences
+    }
+}
@@ -1,7 +1,9 @@
 // sub opmode
-use clap::{value_parser, Arg, ArgAction, Command};
+use clap::{value_parser, Arg, ArgAction, Command, ValueHint};
 use std::path::PathBuf;
 
+use rinex::prelude::SV;
+
 use super::{SHARED_DATA_ARGS, SHARED_GENERAL_ARGS};
 
 pub fn subcommand() -> Command {
@@ -15,12 +17,35 @@ to compare two GNSS receivers to one another, by direct PR or Phase data compari
             Arg::new("file")
                 .value_parser(value_parser!(PathBuf))
                 .value_name("FILEPATH")
+                .value_hint(ValueHint::FilePath)
                 .action(ArgAction::Set)
                 .required(true)
                 .help(
                     "RINEX(B) to substract to a single RINEX file (A), that was previously loaded.",
                 ),
         )
+        .next_help_heading("Differences")
+        .arg(Arg::new("sd").long("sd").action(ArgAction::SetTrue).help(
+            "Between satellites single differences SV-REF, of each receiver.
+Exported as CSV, statistics are summarized in the report.",
+        ))
+        .arg(Arg::new("dd").long("dd").action(ArgAction::SetTrue).help(
+            "Double differences (B-A)(SV) - (B-A)(REF).
+Exported as CSV, statistics are summarized in the report.",
+        ))
+        .arg(
+            Arg::new("ref-sv")
+                .long("ref-sv")
+                .value_name("SV")
+                .value_parser(value_parser!(SV))
+                .action(ArgAction::Set)
+                .help(
+                    "Reference SV of single and double differences.
+Only SV of the same constellation are then differentiated.
+By default, we select one reference per constellation:
+the SV that was observed the most by both receivers.",
+                ),
+        )
         .next_help_heading("Production Environment")
         .args(SHARED_GENERAL_ARGS.iter())
         .next_help_heading("Data context")
@@ -1,17 +1,20 @@
 // Merge opmode
-use clap::{value_parser, Arg, ArgAction, Command};
+use clap::{value_parser, Arg, ArgAction, Command, ValueHint};
 use std::path::PathBuf;
 
 use super::{SHARED_DATA_ARGS, SHARED_GENERAL_ARGS};
 
+use crate::fops::OverlapPolicy;
+
 pub fn subcommand() -> Command {
     Command::new("merge")
         .short_flag('m')
         .long_flag("merge")
-        .arg_required_else_help(true)
-        .about("Merge a RINEX into another and dump result. See -m --help.")
+        .about("Merge all loaded files into one product per kind, and dump result. See -m --help.")
         .long_about(
-            "Merge two files together.
+            "Merge files together.
+Every loaded file, and the possible extra files, are folded into a single product
+per kind (Observation, Navigation, Meteo, Clock, IONEX RINEX and SP3), in chronological order.
 
 1. OBS RINEX example.
 When working with OBS RINEX, you should consider files that come from the same station.
@@ -22,28 +25,52 @@ rinex-cli \\
    -P GPS \\
    -m test_resources/OBS/V3/VLNS0630.22O
 
-1b. CRINEX example.
-When working with CRINEX, the file format is preserved:
+2. Weekly file.
+Fold 7 daily files into a single weekly file:
 
 rinex-cli \\
-  -f test_resources/CRNX/V3/ACOR00ESP_R_20213550000_01D_30S_MO.crx \\
-  -m test_resources/CRNX/V3/BME100HUN_R_20213550000_01D_30S_MO.crx
+   -f \"data/ESBC00DNK_R_2020*_01D_30S_MO.crx.gz\" \\
+   merge
+
+Header compatibility (marker, receiver, observables, sampling) is verified
+and conflicts are reported. Use --strict to abort on conflicts.
+Use --overlap to select what happens to epochs described by several files.
 
-2. Compressed file.
-File compression is also preserved:
+3. CRINEX example.
+When working with CRINEX, the file format is preserved:
 
 rinex-cli \\
-    -f test_resources/CRNX/V3/ESBC00DNK_R_20201770000_01D_30S_MO.crx.gz \\
-    -m test_resources/CRNX/V3/MOJN00DNK_R_20201770000_01D_30S_MO.crx.gz
+  -f test_resources/CRNX/V3/ACOR00ESP_R_20213550000_01D_30S_MO.crx \\
+  -m test_resources/CRNX/V3/BME100HUN_R_20213550000_01D_30S_MO.crx
    ",
         )
         .arg(
             Arg::new("file")
                 .value_parser(value_parser!(PathBuf))
                 .value_name("FILEPATH")
-                .action(ArgAction::Set)
-                .required(true)
-                .help("RINEX file to merge."),
+                .value_hint(ValueHint::FilePath)
+                .action(ArgAction::Append)
+                .num_args(0..)
+                .help("Extra file(s) to merge, on top of the loaded files."),
+        )
+        .arg(
+            Arg::new("overlap")
+                .long("overlap")
+                .value_name("[union,first,last,abort]")
+                .value_parser(value_parser!(OverlapPolicy))
+                .default_value("union")
+                .help("Policy for epochs described by several files. See -m --help.
+\"union\": combine all records (default).
+\"first\": keep the epochs of the earliest file.
+\"last\": keep the epochs of the latest file.
+\"abort\": do not merge overlapping files."),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .help("Abort when files have incompatible headers (marker, receiver, observables, sampling..).
+Otherwise, conflicts are reported and files are merged anyway."),
         )
         .next_help_heading("Production Environment")
         .args(SHARED_GENERAL_ARGS.iter())
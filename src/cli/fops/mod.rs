@@ -69,14 +69,14 @@ Arg::new("timescale")
 
 lazy_static! {
     pub static ref SHARED_DATA_ARGS : Vec<Arg> = vec![
-        Arg::new("PPU")
+        Arg::new("ppu")
             .long("ppu")
             .required(false)
             .value_name("[15M,01H,01D,01Y]")
             .value_parser(value_parser!(PPU))
             .help("Define custom production periodicity (time between two batch/dataset).
 \"15M\": 15' interval, \"01H\": 1 hr interval, \"01D\": 1 day interval, \"01Y\": 1 year interval"),
-        Arg::new("FFU")
+        Arg::new("ffu")
             .long("ffu")
             .required(false)
             .value_name("DDU")
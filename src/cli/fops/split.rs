@@ -1,19 +1,27 @@
 use clap::{value_parser, Arg, ArgAction, Command};
-use gnss_qc::prelude::Epoch;
+use gnss_qc::prelude::{Duration, Epoch};
 
 use super::{SHARED_DATA_ARGS, SHARED_GENERAL_ARGS};
 
 pub fn subcommand() -> Command {
     Command::new("split")
         .arg_required_else_help(true)
-        .about("Split input file(s) at specified Epoch")
+        .about("Split input file(s) at specified Epoch(s), and/or at data gaps")
         .arg(
             Arg::new("split")
                 .value_parser(value_parser!(Epoch))
                 .value_name("EPOCH")
+                .action(ArgAction::Append)
+                .num_args(0..)
+                .help("Epoch(s) (instant) to split at."),
+        )
+        .arg(
+            Arg::new("gap")
+                .long("gap")
+                .value_parser(value_parser!(Duration))
+                .value_name("DURATION")
                 .action(ArgAction::Set)
-                .required(true)
-                .help("Epoch (instant) to split at."),
+                .help("Also split at data gaps larger than this duration."),
         )
         .next_help_heading("Production Environment")
         .args(SHARED_GENERAL_ARGS.iter())
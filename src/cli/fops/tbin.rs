@@ -1,6 +1,6 @@
 use clap::{value_parser, Arg, ArgAction, Command};
 
-use gnss_qc::prelude::Duration;
+use gnss_qc::prelude::{Duration, TimeScale};
 
 use super::{SHARED_DATA_ARGS, SHARED_GENERAL_ARGS};
 
@@ -16,6 +16,28 @@ pub fn subcommand() -> Command {
                 .required(true)
                 .help("Duration"),
         )
+        .arg(
+            Arg::new("align")
+                .long("align")
+                .value_name("TIMESCALE")
+                .value_parser(value_parser!(TimeScale))
+                .num_args(0..=1)
+                .default_missing_value("GPST")
+                .help(
+                    "Align batches to calendar boundaries, in this timescale (GPST by default).
+Hourly batches then start on :00, daily batches on 00:00.
+Otherwise, the first batch starts on the first epoch.",
+                ),
+        )
+        .arg(
+            Arg::new("partial")
+                .long("partial")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Also generate the final batch, when the data stops before its end.
+It is dropped by default.",
+                ),
+        )
         .next_help_heading("Production Environment")
         .args(SHARED_GENERAL_ARGS.iter())
         .next_help_heading("Data context")
@@ -1,6 +1,4 @@
 use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -11,24 +9,42 @@ use anise::{
     prelude::{Frame, Orbit},
 };
 
-use itertools::Itertools;
-
-use clap::{value_parser, Arg, ArgAction, ArgMatches, ColorChoice, Command};
+use clap::{
+    error::ErrorKind, value_parser, Arg, ArgAction, ArgMatches, ColorChoice, Command, ValueHint,
+};
 
+use glob::Pattern;
 use gnss_qc::prelude::{QcConfig, QcContext, QcReportType};
-use rinex::prelude::Epoch;
+use rinex::prelude::{Duration, Epoch};
 
+mod fingerprint;
 mod fops;
 mod positioning;
+mod session;
 mod workspace;
 
+pub use fingerprint::Fingerprint;
 pub use workspace::Workspace;
 
 use fops::{cbin, diff, filegen, merge, split, tbin};
+use session::SessionFile;
+
+use crate::{
+    loader::{Inputs, Selection},
+    preprocessing::{
+        ClockJumpMode, CodeSmoothingOptions, CycleSlipMode, CycleSlipOptions, Interpolation,
+        OutlierAction, OutlierMethod, OutlierOptions, PreprocessingPipeline, PreprocessingReport,
+        ResamplingOptions,
+    },
+    summary::Summary,
+    Error,
+};
 
 pub struct Cli {
     /// Arguments passed by user
     pub matches: ArgMatches,
+    /// Session file content, if any
+    session: Option<String>,
 }
 
 impl Default for Cli {
@@ -46,6 +62,16 @@ pub struct Context {
     /// In differential opmode, this is the ROVER.
     pub data: QcContext,
 
+    /// Run summary, that every operation contributes to
+    pub summary: Summary,
+
+    /// Preprocessing pipeline, that applies to the data context
+    /// and to any other product loaded by file operations.
+    pub preprocessing: PreprocessingPipeline,
+
+    /// Events detected by the preprocessing pipeline
+    pub preprocessing_report: PreprocessingReport,
+
     /// Context name is derived from the primary file loaded in Self,
     /// and mostly used in output products generation.
     pub name: String,
@@ -67,28 +93,26 @@ impl Context {
      * Utility to determine the most major filename stem,
      * to be used as the session workspace
      */
-    pub fn context_stem(data: &QcContext) -> String {
+    pub fn context_stem(data: &QcContext) -> Result<String, Error> {
         let ctx_major_stem: &str = data
             .primary_path()
-            .expect("failed to determine a context name")
+            .ok_or(Error::MissingInput)?
             .file_stem()
-            .expect("failed to determine a context name")
+            .ok_or(Error::MissingInput)?
             .to_str()
-            .expect("failed to determine a context name");
+            .ok_or(Error::MissingInput)?;
 
         /*
          * In case $FILENAME.RNX.gz gz compressed, we extract "$FILENAME".
          * Can use .file_name() once https://github.com/rust-lang/rust/issues/86319  is stabilized
          */
         let primary_stem: Vec<&str> = ctx_major_stem.split('.').collect();
-        primary_stem[0].to_string()
+        Ok(primary_stem[0].to_string())
     }
 
     /// Creates file within session workspace
-    fn create_file(&self, path: &Path) -> std::fs::File {
-        std::fs::File::create(path).unwrap_or_else(|e| {
-            panic!("failed to create {}: {:?}", path.display(), e);
-        })
+    fn create_file(&self, path: &Path) -> std::io::Result<std::fs::File> {
+        std::fs::File::create(path)
     }
 }
 
@@ -109,10 +133,14 @@ Use it to analyze data, perform file operations and resolve navigation solutions
                     .arg(Arg::new("filepath")
                         .long("fp")
                         .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
                         .action(ArgAction::Append)
-                        .required_unless_present("directory")
-                        .help("Load a single file. See --help")
+                        .required_unless_present_any(["directory", "list"])
+                        .help("Load a single file, or all files matching a glob pattern. See --help")
                         .long_help("Use this as many times as needed. 
+Glob patterns are supported (quote them to prevent shell expansion), for example
+--fp \"data/2020/17?/*MO.crx.gz\".
+
 Available operations and following behavior highly depends on input data. 
 Supported formats are:
 - Observation RINEX
@@ -139,11 +167,63 @@ rinex-cli \\
                         .short('d')
                         .long("dir")
                         .value_name("DIRECTORY")
+                        .value_hint(ValueHint::DirPath)
                         .action(ArgAction::Append)
-                        .required_unless_present("filepath")
+                        .required_unless_present_any(["filepath", "list"])
                         .help("Directory recursivel loader. See --help.")
                         .long_help("Use this as many times as needed. Default recursive depth is set to 5,
-but you can extend that with --depth. Refer to -f for more information."))
+but you can extend that with --depth. Refer to -f for more information.
+Use --include and --exclude to select files by name."))
+                    .arg(Arg::new("list")
+                        .long("list")
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .value_parser(value_parser!(PathBuf))
+                        .action(ArgAction::Append)
+                        .required_unless_present_any(["filepath", "directory"])
+                        .help("Load all files listed in this file manifest. See --help.")
+                        .long_help("The manifest describes one file path per line, like --fp does (glob patterns supported).
+Empty lines and lines starting with '#' are ignored. Use this as many times as needed."))
+                    .arg(Arg::new("include")
+                        .long("include")
+                        .value_name("PATTERN")
+                        .value_parser(|s: &str| Pattern::new(s))
+                        .action(ArgAction::Append)
+                        .help("Only load files (from -d) whose name matches this glob pattern. See --help.")
+                        .long_help("Use this as many times as needed: a file is retained when it matches one of the patterns.
+Example: restrict -d to Observation and Navigation files
+   --include \"*MO.crx*\" --include \"*MN.rnx*\""))
+                    .arg(Arg::new("exclude")
+                        .long("exclude")
+                        .value_name("PATTERN")
+                        .value_parser(|s: &str| Pattern::new(s))
+                        .action(ArgAction::Append)
+                        .help("Do not load files (from -d) whose name matches this glob pattern.
+Use this as many times as needed."))
+                    .arg(Arg::new("start")
+                        .long("start")
+                        .value_name("EPOCH")
+                        .value_parser(value_parser!(Epoch))
+                        .help("Only load files that cover this instant or later. See --help.")
+                        .long_help("Files are pre-selected before being parsed.
+The standard (V3) file name is used to determine the time frame of each file.
+When the file name is not standard, only the file header is parsed.
+Example: only load 2020-06-25, out of a year of data
+   -d DATA/2020 --start \"2020-06-25T00:00:00 GPST\" --end \"2020-06-25T23:59:59 GPST\""))
+                    .arg(Arg::new("end")
+                        .long("end")
+                        .value_name("EPOCH")
+                        .value_parser(value_parser!(Epoch))
+                        .help("Only load files that cover this instant or earlier. Refer to --start."))
+                    .arg(Arg::new("station")
+                        .long("station")
+                        .value_name("NAME")
+                        .action(ArgAction::Append)
+                        .help("Only load Observation and Meteo files of this station. See --help.")
+                        .long_help("Use this as many times as needed. Files are pre-selected before being parsed,
+using the station name of the standard (V3) file name, or the MARKER NAME of the file header.
+The name is case insensitive and may be shortened: \"ESBC\" selects \"ESBC00DNK\".
+Other products (Navigation, Clock, SP3..) are not affected."))
                     .arg(Arg::new("depth")
                         .long("depth")
                         .action(ArgAction::Set)
@@ -159,15 +239,43 @@ but you can extend that with --depth. Refer to -f for more information."))
 /YEAR2
      /DOY0
           /STATION1"))
+                    .arg(Arg::new("jobs")
+                        .short('j')
+                        .long("jobs")
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .help("Number of worker threads used to parse input files. See --help.")
+                        .long_help("Input files are parsed in parallel, then stacked in a deterministic order:
+the resulting context does not depend on the number of workers.
+The default is the number of logical CPUs. Use -j 1 for sequential loading."))
                     .arg(Arg::new("quiet")
                         .short('q')
                         .long("quiet")
                         .action(ArgAction::SetTrue)
                         .help("Disable all terminal output. Disables automatic report opener (Web browser)."))
+                    .arg(Arg::new("session")
+                        .long("session")
+                        .value_name("FILE")
+                        .value_hint(ValueHint::FilePath)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("Load options from a JSON session file. See --help.")
+                        .long_help("The session file describes command line options, so you do not have to type them each time.
+Options passed on the command line override the session options. Repeated options (like --fp or -P)
+are appended to the session options. Refer to documentation/Session.md.
+
+Example:
+{
+    \"fp\": [\"data/CRNX/V3/ESBC00DNK_R_20201770000_01D_30S_MO.crx.gz\"],
+    \"P\": [\"!=GLO\", \"decim:5 min\"],
+    \"rx-ecef\": \"3582.105291,532.589731,5232.754805\",
+    \"workspace\": \"/tmp/WORKSPACE\",
+    \"filegen\": { \"gzip\": true, \"agency\": \"IGS\" }
+}"))
                     .arg(Arg::new("workspace")
                         .short('w')
                         .long("workspace")
                         .value_name("FOLDER")
+                        .value_hint(ValueHint::DirPath)
                         .value_parser(value_parser!(PathBuf))
                         .help("Define custom workspace location. See --help.")
                         .long_help("The Workspace is where Output Products are to be generated.
@@ -197,6 +305,13 @@ In file operations (filegen, etc..) we can manually define output filenames with
                 .long("crx2rnx")
                 .action(ArgAction::SetTrue)
                 .help("Any (Observation CRINEX) output products is decompressed to readable RINEX"))
+            .arg(Arg::new("summary")
+                .long("summary")
+                .action(ArgAction::SetTrue)
+                .help("Generate a machine readable run summary (run.json) in the Workspace. See --help.")
+                .long_help("The summary lists the input files and their product type, the preprocessing pipeline,
+the selected opmode, every generated output product, timing, and all warnings and errors.
+It is generated whether the run succeeded or not."))
         .next_help_heading("Report customization")
         .arg(
             Arg::new("report-sum")
@@ -213,6 +328,14 @@ In file operations (filegen, etc..) we can manually define output filenames with
 By default, report synthesis happens once per input set (file combnation and cli options).
 Use this option to force report regeneration.
 This has no effect on file operations that do not synthesize a report."))
+        .arg(
+            Arg::new("explain-cache")
+                .long("explain-cache")
+                .action(ArgAction::SetTrue)
+                .help("Explain why previous report is preserved or regenerated. See --help.")
+                .long_help("The report is preserved when neither the options that affect the output,
+nor the input files (size and modification time) have changed since previous run.
+This lists every difference with previous run."))
         .next_help_heading("Preprocessing")
             .arg(Arg::new("gps-filter")
                 .short('G')
@@ -251,7 +374,23 @@ This has no effect on file operations that do not synthesize a report."))
                 .num_args(1..)
                 .value_delimiter(';')
                 .action(ArgAction::Append)
-                .help("Filter designer. Refer to []."))
+                .help("Filter designer. Refer to documentation/Preprocessor.md.
+Prefix a filter with its product scope (obs:, met:, nav:, clk:, sp3:, ionex:, doris:)
+to restrict it to that product, for example \"obs:decim:300s\".
+Elevation and azimuth masks (\"elev>10\", \"azim:90..180\") are computed
+from the SV orbits and the reference position.
+Signal strength masks (\"snr>=5\", \"GPS:L2,L5:cn0>30\") drop weak signals.
+Signal combinations (\"comb:if:C1C,C5Q\") are synthesized as new observables."))
+            .arg(Arg::new("terrain-mask")
+                .long("terrain-mask")
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .value_parser(value_parser!(PathBuf))
+                .help("Terrain (obstruction) mask. See --help.")
+                .long_help("Masks Observations of SV hidden by the terrain, as seen from the reference position.
+The file describes the minimum elevation per azimuth, one \"azimuth elevation\" pair
+(in degrees) per line. The minimum elevation is interpolated in between.
+Refer to documentation/Preprocessor.md."))
             .arg(Arg::new("nav")
                 .long("nav")
                 .action(ArgAction::Append)
@@ -263,6 +402,16 @@ We support the following:
    3. --nav=testing          retain in-testing (usually non suitable for NAV) SV only
    4. --nav=gps:healthy      apply (1) to GPS only
    5. --nav=bds,gps:testing  apply (3) to BDS+GPS"))
+            .arg(Arg::new("pipeline")
+                .long("pipeline")
+                .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
+                .value_parser(value_parser!(PathBuf))
+                .help("Replay a preprocessing pipeline. See --help.")
+                .long_help("The preprocessing pipeline of a run is described in the run summary (--summary).
+Copy the \"preprocessing\" list into a JSON file to replay the exact same pipeline.
+The replayed steps are applied first, then the preprocessing options of the command line.
+Refer to documentation/Preprocessor.md."))
             .next_help_heading("RINEX Repair")
                 .arg(Arg::new("zero-repair")
                     .short('z')
@@ -276,6 +425,118 @@ To determine whether some null Observations exist (most likely invalid), simply
 generate a first report and study the provided observations.
 The `ppp` solver will most likely encounter Physical Non Sense Errors.
 Null NAV RINEX content is also invalid by definition."))
+                .arg(Arg::new("clock-jumps")
+                    .long("clock-jumps")
+                    .value_name("MODE")
+                    .value_parser(value_parser!(ClockJumpMode))
+                    .help("Detect receiver clock jumps, then \"detect\" (report only) or \"repair\" them. See --help")
+                    .long_help("Receiver clock jumps (typically 1 ms steering) show up as a simultaneous step
+of the code minus phase difference on most satellites.
+   --clock-jumps detect  reports each jump (epoch and magnitude)
+   --clock-jumps repair  also re-aligns all following pseudo ranges and receiver clock offsets,
+                         so they are continuous and consistent with the phase.
+Detected jumps are reported in CSV/clock_jumps.csv. Refer to documentation/Preprocessor.md."))
+                .arg(Arg::new("outliers")
+                    .long("outliers")
+                    .value_name("METHOD")
+                    .value_parser(value_parser!(OutlierMethod))
+                    .help("Reject outliers of each SV and observable, with \"mad\" or \"hampel\" method. See --help")
+                    .long_help("Outliers are detected per SV and observable, using robust statistics.
+   --outliers mad     compares each sample to the median of the complete time series
+   --outliers hampel  compares each sample to the median of a sliding window (--outliers-window)
+A sample is an outlier when it departs from the median by more than --outliers-k
+(scaled) Median Absolute Deviations. Time series are split into arcs at data gaps,
+pseudo ranges are detrended with second order differences before testing.
+Phase observations are not concerned (see --cs).
+Rejected samples are reported per SV and observable in CSV/outliers.csv.
+Refer to documentation/Preprocessor.md."))
+                .arg(Arg::new("outliers-k")
+                    .long("outliers-k")
+                    .value_name("K")
+                    .value_parser(value_parser!(f64))
+                    .default_value("3.5")
+                    .help("Outliers threshold, in (scaled) Median Absolute Deviations"))
+                .arg(Arg::new("outliers-window")
+                    .long("outliers-window")
+                    .value_name("SAMPLES")
+                    .value_parser(value_parser!(usize))
+                    .default_value("5")
+                    .help("Half width of the sliding window, in samples, in --outliers hampel mode"))
+                .arg(Arg::new("outliers-action")
+                    .long("outliers-action")
+                    .value_name("ACTION")
+                    .value_parser(value_parser!(OutlierAction))
+                    .default_value("drop")
+                    .help("\"drop\" outliers, or \"mask\" them (preserved untouched, not used by the solvers)"))
+                .arg(Arg::new("cycle-slips")
+                    .long("cs")
+                    .value_name("MODE")
+                    .value_parser(value_parser!(CycleSlipMode))
+                    .help("Detect phase cycle slips, then \"flag\", \"split\" or \"repair\" them. See --help")
+                    .long_help("Cycle slips are detected from the Loss of Lock indicator, and from the Geometry Free (GF)
+and Melbourne-Wübbena (MW) combinations of the two highest frequencies of each satellite.
+   --cs flag    sets the Loss of Lock indicator of the slipped phase observations
+   --cs split   flags, then splits phase arcs at every slip and discards arcs shorter than --cs-min-arc
+   --cs repair  removes the estimated jump from the phase observations, when both GF and MW
+                are available. Other slips are flagged.
+Detected slips are reported per SV and signal, in the report and in CSV/cycle_slips.csv.
+Refer to documentation/Preprocessor.md."))
+                .arg(Arg::new("cs-gf")
+                    .long("cs-gf")
+                    .value_name("METERS")
+                    .value_parser(value_parser!(f64))
+                    .default_value("0.05")
+                    .help("Geometry Free combination cycle slip threshold, in meters"))
+                .arg(Arg::new("cs-mw")
+                    .long("cs-mw")
+                    .value_name("CYCLES")
+                    .value_parser(value_parser!(f64))
+                    .default_value("4.0")
+                    .help("Melbourne-Wübbena combination cycle slip threshold, in wide lane cycles"))
+                .arg(Arg::new("cs-min-arc")
+                    .long("cs-min-arc")
+                    .value_name("EPOCHS")
+                    .value_parser(value_parser!(usize))
+                    .default_value("10")
+                    .help("Minimal phase arc length, in epochs, in --cs split mode"))
+                .arg(Arg::new("hatch")
+                    .long("hatch")
+                    .value_name("EPOCHS")
+                    .value_parser(value_parser!(usize))
+                    .help("Smooth pseudo ranges with the carrier phase (Hatch filter), over this many epochs. See --help")
+                    .long_help("Each pseudo range is replaced by its carrier smoothed value, using the phase
+of the same carrier (preferably the same tracking code, for example L1C for C1C).
+The filter is reset on Loss of Lock, data gaps and missing phase observations.
+Combine with --cs to reset the filter on detected cycle slips as well.
+Smoothed pseudo ranges apply to every opmode (report, file operations, ppp), and the
+smoothing summary is written in CSV/code_smoothing.csv. Refer to documentation/Preprocessor.md."))
+            .next_help_heading("Resampling")
+                .arg(Arg::new("resample")
+                    .long("resample")
+                    .value_name("DURATION")
+                    .value_parser(value_parser!(Duration))
+                    .help("Resample OBS, Meteo and Clock RINEX onto a regular time grid. See --help")
+                    .long_help("Grid epochs are exact multiples of this period in GPST, for example --resample \"30 s\".
+Each time series is interpolated onto the grid with --resample-method.
+No value is produced across data gaps larger than --resample-max-gap.
+Resampling is the last preprocessing step. Refer to documentation/Preprocessor.md."))
+                .arg(Arg::new("resample-method")
+                    .long("resample-method")
+                    .value_name("METHOD")
+                    .value_parser(value_parser!(Interpolation))
+                    .default_value("linear")
+                    .help("Interpolation method: \"nearest\", \"linear\" or \"lagrange\""))
+                .arg(Arg::new("resample-order")
+                    .long("resample-order")
+                    .value_name("ORDER")
+                    .value_parser(value_parser!(usize))
+                    .default_value("5")
+                    .help("Interpolation order, in --resample-method lagrange"))
+                .arg(Arg::new("resample-max-gap")
+                    .long("resample-max-gap")
+                    .value_name("DURATION")
+                    .value_parser(value_parser!(Duration))
+                    .help("Maximal data gap to interpolate across. Twice the grid period by default."))
             .next_help_heading("Receiver Antenna")
                 .arg(Arg::new("rx-ecef")
                     .long("rx-ecef")
@@ -290,16 +551,43 @@ Otherwise it gets automatically picked up."))
                 .next_help_heading("Exclusive Opmodes: you can only run one at a time.")
                 .subcommand(filegen::subcommand());
 
-        let cmd = cmd
+        let mut cmd = cmd
             .subcommand(merge::subcommand())
             .subcommand(positioning::ppp_subcommand())
             .subcommand(positioning::rtk_subcommand())
             .subcommand(split::subcommand())
             .subcommand(diff::subcommand())
             .subcommand(cbin::subcommand())
-            .subcommand(tbin::subcommand());
-        Self {
-            matches: cmd.get_matches(),
+            .subcommand(tbin::subcommand())
+            .args_override_self(true);
+
+        let opmodes = cmd
+            .get_subcommands()
+            .map(|opmode| opmode.get_name().to_string())
+            .collect::<Vec<_>>();
+
+        // command line options override session options
+        for opmode in opmodes.iter() {
+            cmd = cmd.mut_subcommand(opmode, |opmode| opmode.args_override_self(true));
+        }
+
+        let args = std::env::args_os().collect::<Vec<_>>();
+
+        match SessionFile::path(&args) {
+            Some(path) => match SessionFile::from_file(Path::new(&path), &cmd) {
+                Ok(session) => {
+                    let merged = session.merge(&args, &cmd);
+                    Self {
+                        matches: cmd.get_matches_from(merged),
+                        session: Some(session.content),
+                    }
+                },
+                Err(e) => cmd.error(ErrorKind::InvalidValue, e).exit(),
+            },
+            None => Self {
+                matches: cmd.get_matches_from(args),
+                session: None,
+            },
         }
     }
 
@@ -312,6 +600,70 @@ Otherwise it gets automatically picked up."))
         }
     }
 
+    /// Number of worker threads to parse input files
+    pub fn jobs(&self) -> usize {
+        match self.matches.get_one::<usize>("jobs") {
+            Some(jobs) => *jobs,
+            None => std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        }
+    }
+
+    /// True if terminal output should be reduced to the minimum
+    pub fn quiet(&self) -> bool {
+        self.matches.get_flag("quiet")
+    }
+
+    /// Returns [Inputs] describing the ROVER dataset
+    pub fn rover_inputs(&self) -> Inputs {
+        Inputs {
+            files: self.rover_files().into_iter().cloned().collect(),
+            lists: self.rover_lists().into_iter().cloned().collect(),
+            directories: self.rover_directories().into_iter().cloned().collect(),
+            max_depth: self.recursive_depth(),
+            include: self.name_patterns("include"),
+            exclude: self.name_patterns("exclude"),
+            selection: self.selection(),
+        }
+    }
+
+    /// Returns time window and station pre-selection
+    fn selection(&self) -> Selection {
+        Selection {
+            stations: self.stations().into_iter().cloned().collect(),
+            start: self.matches.get_one::<Epoch>("start").copied(),
+            end: self.matches.get_one::<Epoch>("end").copied(),
+        }
+    }
+
+    /// Returns selected --station names
+    fn stations(&self) -> Vec<&String> {
+        if let Some(stations) = self.matches.get_many::<String>("station") {
+            stations.collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns file name patterns for -d
+    fn name_patterns(&self, id: &str) -> Vec<Pattern> {
+        if let Some(patterns) = self.matches.get_many::<Pattern>(id) {
+            patterns.cloned().collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns ROVER file lists --list
+    pub fn rover_lists(&self) -> Vec<&PathBuf> {
+        if let Some(lists) = self.matches.get_many::<PathBuf>("list") {
+            lists.collect()
+        } else {
+            Vec::new()
+        }
+    }
+
     /// Returns individual input ROVER -d
     pub fn rover_directories(&self) -> Vec<&String> {
         if let Some(dirs) = self.matches.get_many::<String>("directory") {
@@ -366,6 +718,16 @@ Otherwise it gets automatically picked up."))
         }
     }
 
+    /// Returns preprocessing pipeline to replay, if any
+    pub fn pipeline_file(&self) -> Option<&PathBuf> {
+        self.matches.get_one::<PathBuf>("pipeline")
+    }
+
+    /// Returns terrain mask file, if any
+    pub fn terrain_mask_file(&self) -> Option<&PathBuf> {
+        self.matches.get_one::<PathBuf>("terrain-mask")
+    }
+
     /// Returns list of NAV filters
     pub fn nav_filters(&self) -> Vec<&String> {
         if let Some(filters) = self.matches.get_many::<String>("nav") {
@@ -403,45 +765,107 @@ Otherwise it gets automatically picked up."))
         self.matches.get_flag("zero-repair")
     }
 
+    /// Returns clock jumps mode, if clock jumps detection is requested
+    pub fn clock_jumps(&self) -> Option<ClockJumpMode> {
+        self.matches
+            .get_one::<ClockJumpMode>("clock-jumps")
+            .copied()
+    }
+
+    /// Returns outliers options, if outliers rejection is requested
+    pub fn outliers(&self) -> Option<OutlierOptions> {
+        let method = *self.matches.get_one::<OutlierMethod>("outliers")?;
+        Some(OutlierOptions {
+            method,
+            action: *self.matches.get_one::<OutlierAction>("outliers-action")?,
+            threshold: *self.matches.get_one::<f64>("outliers-k")?,
+            half_window: *self.matches.get_one::<usize>("outliers-window")?,
+        })
+    }
+
+    /// Returns code smoothing options, if code smoothing is requested
+    pub fn code_smoothing(&self) -> Option<CodeSmoothingOptions> {
+        let window = *self.matches.get_one::<usize>("hatch")?;
+        Some(CodeSmoothingOptions { window })
+    }
+
+    /// Returns resampling options, if resampling is requested
+    pub fn resampling(&self) -> Option<ResamplingOptions> {
+        let period = *self.matches.get_one::<Duration>("resample")?;
+        let max_gap = self
+            .matches
+            .get_one::<Duration>("resample-max-gap")
+            .copied()
+            .unwrap_or(period * 2.0);
+
+        Some(ResamplingOptions {
+            period_s: period.to_seconds(),
+            method: *self.matches.get_one::<Interpolation>("resample-method")?,
+            order: *self.matches.get_one::<usize>("resample-order")?,
+            max_gap_s: max_gap.to_seconds(),
+        })
+    }
+
+    /// Returns cycle slips options, if cycle slips detection is requested
+    pub fn cycle_slips(&self) -> Option<CycleSlipOptions> {
+        let mode = *self.matches.get_one::<CycleSlipMode>("cycle-slips")?;
+        Some(CycleSlipOptions {
+            mode,
+            gf_threshold_m: *self.matches.get_one::<f64>("cs-gf")?,
+            mw_threshold_cycles: *self.matches.get_one::<f64>("cs-mw")?,
+            min_arc_epochs: *self.matches.get_one::<usize>("cs-min-arc")?,
+        })
+    }
+
     /// Parse 3D coordinates (tuplets)
-    fn parse_3d_coordinates(desc: &String) -> (f64, f64, f64) {
+    fn parse_3d_coordinates(desc: &String) -> Result<(f64, f64, f64), Error> {
         let content = desc.split(',').collect::<Vec<&str>>();
         if content.len() < 3 {
-            panic!("expecting x, y and z coordinates (3D)");
+            return Err(Error::Configuration(format!(
+                "expecting x, y and z coordinates (3D), got \"{}\"",
+                desc
+            )));
         }
 
         let x = f64::from_str(content[0].trim())
-            .unwrap_or_else(|e| panic!("failed to parse x coordinates: {}", e));
+            .map_err(|e| Error::Configuration(format!("failed to parse x coordinates: {}", e)))?;
 
         let y = f64::from_str(content[1].trim())
-            .unwrap_or_else(|e| panic!("failed to parse y coordinates: {}", e));
+            .map_err(|e| Error::Configuration(format!("failed to parse y coordinates: {}", e)))?;
 
         let z = f64::from_str(content[2].trim())
-            .unwrap_or_else(|e| panic!("failed to parse z coordinates: {}", e));
+            .map_err(|e| Error::Configuration(format!("failed to parse z coordinates: {}", e)))?;
 
-        (x, y, z)
+        Ok((x, y, z))
     }
 
     /// Returns possible ECEF km triplet manually defined
-    fn manual_ecef_km(&self) -> Option<(f64, f64, f64)> {
-        let desc = self.matches.get_one::<String>("rx-ecef")?;
-        let ecef = Self::parse_3d_coordinates(desc);
-        Some(ecef)
+    fn manual_ecef_km(&self) -> Result<Option<(f64, f64, f64)>, Error> {
+        match self.matches.get_one::<String>("rx-ecef") {
+            Some(desc) => Ok(Some(Self::parse_3d_coordinates(desc)?)),
+            None => Ok(None),
+        }
     }
 
-    fn manual_geodetic_ddeg_ddeg_km(&self) -> Option<(f64, f64, f64)> {
-        let desc = self.matches.get_one::<String>("rx-geo")?;
-        let geo = Self::parse_3d_coordinates(desc);
-        Some(geo)
+    fn manual_geodetic_ddeg_ddeg_km(&self) -> Result<Option<(f64, f64, f64)>, Error> {
+        match self.matches.get_one::<String>("rx-geo") {
+            Some(desc) => Ok(Some(Self::parse_3d_coordinates(desc)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// True if RX Position was manually defined
+    pub fn manual_rx_orbit_defined(&self) -> bool {
+        self.matches.get_one::<String>("rx-ecef").is_some()
+            || self.matches.get_one::<String>("rx-geo").is_some()
     }
 
     /// Returns RX Position possibly specified by user, in km ECEF.
-    pub fn manual_rx_orbit(&self, epoch: Epoch, frame: Frame) -> Option<Orbit> {
-        if let Some((x0_km, y0_km, z0_km)) = self.manual_ecef_km() {
+    pub fn manual_rx_orbit(&self, epoch: Epoch, frame: Frame) -> Result<Option<Orbit>, Error> {
+        if let Some((x0_km, y0_km, z0_km)) = self.manual_ecef_km()? {
             let pos_vel = Vector6::new(x0_km, y0_km, z0_km, 0.0, 0.0, 0.0);
-            Some(Orbit::from_cartesian_pos_vel(pos_vel, epoch, frame))
-        } else {
-            let (lat_ddeg, long_ddeg, alt_km) = self.manual_geodetic_ddeg_ddeg_km()?;
+            Ok(Some(Orbit::from_cartesian_pos_vel(pos_vel, epoch, frame)))
+        } else if let Some((lat_ddeg, long_ddeg, alt_km)) = self.manual_geodetic_ddeg_ddeg_km()? {
             let orbit = Orbit::try_latlongalt(
                 lat_ddeg,
                 long_ddeg,
@@ -450,9 +874,32 @@ Otherwise it gets automatically picked up."))
                 epoch,
                 frame,
             )
-            .unwrap_or_else(|e| panic!("physical error: {}", e));
-            Some(orbit)
+            .map_err(|e| Error::Configuration(format!("physical error: {}", e)))?;
+            Ok(Some(orbit))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the reference position of this data context:
+    /// manually defined, or picked up from the context itself.
+    pub fn reference_rx_orbit(&self, ctx: &QcContext) -> Result<Option<Orbit>, Error> {
+        if let Some(t0) = ctx.observation().and_then(|obs| obs.first_epoch()) {
+            if let Some(rx_orbit) = self.manual_rx_orbit(t0, ctx.earth_cef)? {
+                return Ok(Some(rx_orbit));
+            }
         }
+        Ok(ctx.reference_rx_orbit())
+    }
+
+    /// Returns the selected opmode. The default opmode is "qc" (analysis report).
+    pub fn opmode(&self) -> &str {
+        self.matches.subcommand_name().unwrap_or("qc")
+    }
+
+    /// True if run summary (run.json) should be generated
+    pub fn run_summary(&self) -> bool {
+        self.matches.get_flag("summary")
     }
 
     /// True if File Operations to generate data is being deployed
@@ -472,31 +919,21 @@ Otherwise it gets automatically picked up."))
         self.matches.get_flag("report-force")
     }
 
-    /// Hash all critical parameters, defining a user session uniquely
-    pub fn hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        let mut string = self
-            .rover_directories()
-            .into_iter()
-            .sorted()
-            .chain(self.rover_files().into_iter().sorted())
-            .chain(self.preprocessing().into_iter().sorted())
-            .join(",");
-
-        if let Some(custom) = self.custom_output_name() {
-            string.push_str(custom);
-        }
-
-        // if let Some(geo) = self.manual_geodetic_ddeg() {
-        //     string.push_str(&format!("{:?}", geo));
-        // }
-
-        // if let Some(ecef) = self.manual_ecef_km() {
-        //     string.push_str(&format!("{:?}", ecef));
-        // }
+    /// True if report caching decisions should be explained
+    pub fn explain_cache(&self) -> bool {
+        self.matches.get_flag("explain-cache")
+    }
 
-        string.hash(&mut hasher);
-        hasher.finish()
+    /// Returns the [Fingerprint] of this session: all options that affect
+    /// the output, the preprocessing pipeline, and the current state
+    /// of the input files that were loaded.
+    pub fn fingerprint(&self, ctx: &Context) -> Fingerprint {
+        Fingerprint::new(
+            &self.matches,
+            self.session.as_ref(),
+            &ctx.preprocessing,
+            &ctx.summary.inputs(),
+        )
     }
 
     /// Returns QcConfig from command line
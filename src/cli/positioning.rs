@@ -1,5 +1,5 @@
 // Positioning OPMODE
-use clap::{value_parser, Arg, ArgAction, Command};
+use clap::{value_parser, Arg, ArgAction, Command, ValueHint};
 use rinex::prelude::Duration;
 
 fn shared_args(cmd: Command) -> Command {
@@ -8,6 +8,7 @@ fn shared_args(cmd: Command) -> Command {
             .short('c')
             .long("cfg")
             .value_name("FILE")
+            .value_hint(ValueHint::FilePath)
             .required(false)
             .action(ArgAction::Append)
             .help("Position Solver configuration file (JSON). See --help.")
@@ -143,6 +144,7 @@ CGGTTS and other options still apply."
             Arg::new("fp")
                 .long("fp")
                 .value_name("FILE")
+                .value_hint(ValueHint::FilePath)
                 .action(ArgAction::Append)
                 .required_unless_present("dir")
                 .help("Pass any RINEX file for remote base station"),
@@ -151,6 +153,7 @@ CGGTTS and other options still apply."
             Arg::new("dir")
                 .short('d')
                 .value_name("DIR")
+                .value_hint(ValueHint::DirPath)
                 .action(ArgAction::Append)
                 .required_unless_present("fp")
                 .help("Pass any directory for remote base station"),
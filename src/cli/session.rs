@@ -0,0 +1,298 @@
+//! Session file: describes command line options in a JSON file
+use std::{ffi::OsString, fs::read_to_string, path::Path};
+
+use clap::{Arg, Command, ValueHint};
+use serde_json::Value;
+
+/// Session file content, converted to command line tokens
+pub struct SessionFile {
+    /// Raw content, that contributes to the session fingerprint
+    pub content: String,
+    /// Options, prior the opmode
+    tokens: Vec<String>,
+    /// Opmode, its positional arguments and its options
+    opmode: Option<(String, Vec<String>, Vec<String>)>,
+}
+
+/// Converts an option name to its command line form
+fn option_token(key: &str) -> String {
+    if key.chars().count() == 1 {
+        format!("-{}", key)
+    } else {
+        format!("--{}", key)
+    }
+}
+
+/// Returns true when this argument describes a file or directory
+fn is_path(arg: &Arg) -> bool {
+    matches!(
+        arg.get_value_hint(),
+        ValueHint::FilePath | ValueHint::DirPath | ValueHint::AnyPath
+    )
+}
+
+/// Returns the option of this [Command], by long name (or short name, for single letter options)
+fn find_option<'a>(cmd: &'a Command, key: &str) -> Option<&'a Arg> {
+    let mut chars = key.chars();
+
+    let short = match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    };
+
+    cmd.get_arguments()
+        .find(|arg| arg.get_long() == Some(key) || (short.is_some() && arg.get_short() == short))
+}
+
+/// Relative paths are expressed from the session file directory
+fn resolve_path(base: &Path, value: &str) -> String {
+    let path = Path::new(value);
+
+    if path.is_absolute() {
+        value.to_string()
+    } else {
+        base.join(path).to_string_lossy().to_string()
+    }
+}
+
+/// Converts one option to command line tokens.
+/// Path values are resolved against `base`, when defined.
+fn append_tokens(
+    key: &str,
+    value: &Value,
+    base: Option<&Path>,
+    tokens: &mut Vec<String>,
+) -> Result<(), String> {
+    match value {
+        Value::Null | Value::Bool(false) => {},
+        Value::Bool(true) => tokens.push(option_token(key)),
+        Value::String(value) => {
+            tokens.push(option_token(key));
+            match base {
+                Some(base) => tokens.push(resolve_path(base, value)),
+                None => tokens.push(value.clone()),
+            }
+        },
+        Value::Number(value) => {
+            tokens.push(option_token(key));
+            tokens.push(value.to_string());
+        },
+        Value::Array(values) => {
+            // repeated option
+            for value in values {
+                if value.is_array() || value.is_object() {
+                    return Err(format!("\"{}\": nested values are not supported", key));
+                }
+                append_tokens(key, value, base, tokens)?;
+            }
+        },
+        Value::Object(_) => {
+            return Err(format!("\"{}\" is not a known opmode", key));
+        },
+    }
+    Ok(())
+}
+
+/// Converts positional arguments to command line tokens.
+/// `paths` tells which positional arguments describe a path:
+/// the last one applies to all remaining values.
+fn append_positional(
+    value: &Value,
+    paths: &[bool],
+    base: &Path,
+    tokens: &mut Vec<String>,
+) -> Result<(), String> {
+    match value {
+        Value::String(value) => {
+            let index = tokens.len().min(paths.len().saturating_sub(1));
+
+            if paths.get(index).copied().unwrap_or(false) {
+                tokens.push(resolve_path(base, value));
+            } else {
+                tokens.push(value.clone());
+            }
+        },
+        Value::Number(value) => tokens.push(value.to_string()),
+        Value::Array(values) => {
+            for value in values {
+                if value.is_array() {
+                    return Err("\"args\": nested values are not supported".to_string());
+                }
+                append_positional(value, paths, base, tokens)?;
+            }
+        },
+        _ => return Err("\"args\": expecting a string or a list of strings".to_string()),
+    }
+    Ok(())
+}
+
+impl SessionFile {
+    /// Parses a session file, that describes options of this [Command].
+    /// Relative paths are expressed from the session file directory.
+    pub fn from_file(path: &Path, cmd: &Command) -> Result<Self, String> {
+        let content = read_to_string(path)
+            .map_err(|e| format!("failed to read session file \"{}\": {}", path.display(), e))?;
+
+        let value: Value = serde_json::from_str(&content)
+            .map_err(|e| format!("invalid session file \"{}\": {}", path.display(), e))?;
+
+        let options = value
+            .as_object()
+            .ok_or_else(|| "session file should describe a JSON object".to_string())?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        let mut tokens = Vec::new();
+        let mut opmode = None;
+
+        for (key, value) in options {
+            if key == "session" {
+                return Err("session files cannot be nested".to_string());
+            }
+
+            if let Some(subcommand) = cmd.find_subcommand(key) {
+                if opmode.is_some() {
+                    return Err("only one opmode can be defined".to_string());
+                }
+
+                let options = value
+                    .as_object()
+                    .ok_or_else(|| format!("\"{}\" options should be a JSON object", key))?;
+
+                let mut positional = Vec::new();
+                let mut sub_tokens = Vec::new();
+
+                if let Some(args) = options.get("args") {
+                    let paths = subcommand
+                        .get_positionals()
+                        .map(is_path)
+                        .collect::<Vec<_>>();
+                    append_positional(args, &paths, base, &mut positional)?;
+                }
+
+                for (key, value) in options.iter().filter(|(key, _)| *key != "args") {
+                    let path_value = find_option(subcommand, key).map_or(false, is_path);
+                    append_tokens(key, value, path_value.then_some(base), &mut sub_tokens)?;
+                }
+
+                opmode = Some((key.clone(), positional, sub_tokens));
+            } else {
+                let path_value = find_option(cmd, key).map_or(false, is_path);
+                append_tokens(key, value, path_value.then_some(base), &mut tokens)?;
+            }
+        }
+
+        Ok(Self {
+            content,
+            tokens,
+            opmode,
+        })
+    }
+
+    /// Returns the session file path, if defined in these raw arguments
+    pub fn path(args: &[OsString]) -> Option<OsString> {
+        let mut args = args.iter().skip(1);
+
+        while let Some(arg) = args.next() {
+            let arg_str = arg.to_string_lossy();
+
+            if arg_str == "--session" {
+                return args.next().cloned();
+            }
+
+            if let Some(path) = arg_str.strip_prefix("--session=") {
+                return Some(OsString::from(path));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the opmode (subcommand) selected in these raw arguments, and its position.
+    /// The opmode is identified by clap itself, then located by its name or flags,
+    /// skipping option values that happen to match.
+    fn user_opmode(args: &[OsString], cmd: &Command) -> Option<(String, usize)> {
+        let matches = cmd
+            .clone()
+            .ignore_errors(true)
+            .try_get_matches_from(args)
+            .ok()?;
+
+        let name = matches.subcommand_name()?.to_string();
+        let subcommand = cmd.find_subcommand(&name)?;
+
+        let mut tokens = vec![name.clone()];
+        tokens.extend(subcommand.get_all_aliases().map(|alias| alias.to_string()));
+
+        if let Some(short) = subcommand.get_short_flag() {
+            tokens.push(format!("-{}", short));
+        }
+
+        if let Some(long) = subcommand.get_long_flag() {
+            tokens.push(format!("--{}", long));
+        }
+
+        // previous token is an option that expects a value
+        let is_value = |previous: &str| {
+            let key = if let Some(long) = previous.strip_prefix("--") {
+                if long.contains('=') {
+                    return false;
+                }
+                long
+            } else if let Some(short) = previous.strip_prefix('-') {
+                if short.chars().count() != 1 {
+                    return false;
+                }
+                short
+            } else {
+                return false;
+            };
+
+            find_option(cmd, key).map_or(false, |arg| arg.get_action().takes_values())
+        };
+
+        let position = (1..args.len()).find(|pos| {
+            let token = args[*pos].to_string_lossy();
+            tokens.iter().any(|t| *t == token) && !is_value(&args[pos - 1].to_string_lossy())
+        })?;
+
+        Some((name, position))
+    }
+
+    /// Merges this session with the command line arguments.
+    /// Session options come first, so command line options override them.
+    /// The session opmode only applies when no other opmode was selected.
+    /// When the same opmode is selected, positional arguments are those of the command line.
+    pub fn merge(&self, args: &[OsString], cmd: &Command) -> Vec<OsString> {
+        let mut merged = Vec::<OsString>::with_capacity(args.len() + self.tokens.len());
+
+        // binary name
+        merged.extend(args.iter().take(1).cloned());
+        merged.extend(self.tokens.iter().map(OsString::from));
+
+        match (&self.opmode, Self::user_opmode(args, cmd)) {
+            (Some((opmode, _, sub_tokens)), Some((user_opmode, pos))) => {
+                merged.extend(args[1..=pos].iter().cloned());
+
+                if user_opmode == *opmode {
+                    merged.extend(sub_tokens.iter().map(OsString::from));
+                } else {
+                    debug!("session file opmode \"{}\" overridden", opmode);
+                }
+
+                merged.extend(args[pos + 1..].iter().cloned());
+            },
+            (Some((opmode, positional, sub_tokens)), None) => {
+                merged.extend(args.iter().skip(1).cloned());
+                merged.push(OsString::from(opmode));
+                merged.extend(positional.iter().map(OsString::from));
+                merged.extend(sub_tokens.iter().map(OsString::from));
+            },
+            (None, _) => {
+                merged.extend(args.iter().skip(1).cloned());
+            },
+        }
+
+        merged
+    }
+}
@@ -19,7 +19,7 @@ impl Workspace {
     ///  2. from -w workspace CLI argument
     ///  3. or defaults to ./WORSPACE, that exists within this Git repo.
     /// Refer to Wiki Pages.
-    pub fn new(session: &str, cli: &Cli) -> Self {
+    pub fn new(session: &str, cli: &Cli) -> std::io::Result<Self> {
         let root = match std::env::var("RINEX_WORKSPACE") {
             Ok(path) => Path::new(&path).join(session).to_path_buf(),
             _ => match cli.matches.get_one::<PathBuf>("workspace") {
@@ -28,33 +28,35 @@ impl Workspace {
             },
         };
         // make sure workspace does exists, otherwise create it
-        create_dir_all(&root).unwrap_or_else(|e| {
-            panic!(
+        create_dir_all(&root).map_err(|e| {
+            error!(
                 "failed to create session workspace \"{}\": {}",
                 root.display(),
                 e
-            )
-        });
+            );
+            e
+        })?;
         info!("session workspace is \"{}\"", root.to_string_lossy());
-        Self {
+        Ok(Self {
             root: root.to_path_buf(),
-        }
+        })
     }
     /// Creates subdirectory within self.
-    /// Will panic on write permission issues.
-    pub fn create_subdir(&self, dir: &str) {
-        create_dir_all(self.root.join(dir)).unwrap_or_else(|e| {
-            panic!("failed to create directory {} within workspace: {}", dir, e)
-        });
+    pub fn create_subdir(&self, dir: &str) -> std::io::Result<()> {
+        create_dir_all(self.root.join(dir)).map_err(|e| {
+            error!("failed to create directory {} within workspace: {}", dir, e);
+            e
+        })
     }
     /// Creates new file within this session.
-    /// Will panic on write permission issues.
-    pub fn create_file(&self, filename: &str) -> File {
+    pub fn create_file(&self, filename: &str) -> std::io::Result<File> {
         let fullpath = self.root.join(filename).to_string_lossy().to_string();
-        let fd = File::create(&fullpath)
-            .unwrap_or_else(|e| panic!("failed to create new file {}: {}", filename, e));
+        let fd = File::create(&fullpath).map_err(|e| {
+            error!("failed to create new file {}: {}", filename, e);
+            e
+        })?;
         info!("{} has been generated", fullpath);
-        fd
+        Ok(fd)
     }
     /// Opens root path with prefered web browser
     #[cfg(target_os = "linux")]
@@ -72,19 +74,20 @@ impl Workspace {
     #[cfg(target_os = "macos")]
     pub fn open_with_web_browser(&self) {
         let fullpath = self.root.to_string_lossy().to_string();
-        Command::new("open")
-            .args(&[fullpath])
-            .output()
-            .expect("open() failed, can't open HTML content automatically");
+        if let Err(e) = Command::new("open").args(&[fullpath]).output() {
+            warn!("failed to open HTML content automatically: {}", e);
+        }
     }
     /// Opens root path with prefered web browser
     #[cfg(target_os = "windows")]
     pub fn open_with_web_browser(&self) {
         let fullpath = self.root.to_string_lossy().to_string();
-        Command::new("cmd")
+        if let Err(e) = Command::new("cmd")
             .arg("/C")
             .arg(format!(r#"start {}"#, fullpath))
             .output()
-            .expect("failed to open generated HTML content");
+        {
+            warn!("failed to open generated HTML content: {}", e);
+        }
     }
 }
@@ -6,7 +6,11 @@ use gnss_qc::prelude::{
     ProductType, TimeScale, Timeshift,
 };
 
-use crate::{cli::Context, fops::dump_rinex_auto_generated_name, Error};
+use crate::{
+    cli::Context,
+    fops::{dump_rinex_auto_generated_name, dump_sp3_auto_generated_name},
+    Error,
+};
 
 /// Constellation / timescale batch creation
 pub fn constell_timescale_binning(ctx: &Context, submatches: &ArgMatches) -> Result<(), Error> {
@@ -24,7 +28,10 @@ pub fn constell_timescale_binning(ctx: &Context, submatches: &ArgMatches) -> Res
     };
 
     if ts_binning && prefered_ts.is_some() {
-        panic!("timescale binning (--ts) and prefered timescale (--timescale) are incompatible!");
+        return Err(Error::Configuration(
+            "timescale binning (--ts) and prefered timescale (--timescale) are incompatible"
+                .to_string(),
+        ));
     }
 
     for product in [
@@ -37,7 +44,7 @@ pub fn constell_timescale_binning(ctx: &Context, submatches: &ArgMatches) -> Res
             // split on a constellation basis
             for constellation in rinex.constellations_iter() {
                 let custom_subdir = format!("{:X}", constellation);
-                ctx.workspace.create_subdir(&custom_subdir);
+                ctx.workspace.create_subdir(&custom_subdir)?;
 
                 // design filter
                 debug!("{} constellation binning..", constellation);
@@ -86,7 +93,7 @@ pub fn constell_timescale_binning(ctx: &Context, submatches: &ArgMatches) -> Res
                     &focused,
                     gzip,
                     Some(custom_subdir),
-                );
+                )?;
             }
         }
     }
@@ -95,7 +102,7 @@ pub fn constell_timescale_binning(ctx: &Context, submatches: &ArgMatches) -> Res
         // split per constellation
         for constellation in sp3.constellations_iter() {
             let custom_subdir = format!("{:X}", constellation);
-            ctx.workspace.create_subdir(&custom_subdir);
+            ctx.workspace.create_subdir(&custom_subdir)?;
 
             // design filter
             debug!("{} constellation binning..", constellation);
@@ -134,9 +141,7 @@ pub fn constell_timescale_binning(ctx: &Context, submatches: &ArgMatches) -> Res
                 focused.timeshift_mut(ts);
             }
 
-            // filename
-            // let standard_name = focused.standard_filename();
-            // dump_sp3(&standard_name, gzip, Some(custom_dir));
+            dump_sp3_auto_generated_name(ctx, &focused, submatches, Some(custom_subdir))?;
         }
     }
 
@@ -20,7 +20,7 @@ pub fn dump_context_csv(
 ) -> Result<(), Error> {
     let ctx_data = &ctx.data;
 
-    ctx.workspace.create_subdir("CSV");
+    ctx.workspace.create_subdir("CSV")?;
 
     // OBS RINEX
     if let Some(rinex) = ctx_data.rinex(ProductType::Observation) {
@@ -39,11 +39,13 @@ pub fn dump_context_csv(
             ProductType::Observation,
             output.display()
         );
+
+        ctx.summary.output(&output);
     }
 
     // NAV RINEX
     if let Some(brdc) = ctx_data.rinex(ProductType::BroadcastNavigation) {
-        ctx.workspace.create_subdir("BRDC");
+        ctx.workspace.create_subdir("BRDC")?;
 
         let prod = custom_prod_attributes(brdc, submatches);
         let output_name = output_filename(brdc, matches, submatches, prod);
@@ -58,8 +60,10 @@ pub fn dump_context_csv(
             output.display()
         );
 
+        ctx.summary.output(&output);
+
         if let Some(obs) = ctx_data.rinex(ProductType::Observation) {
-            ctx.workspace.create_subdir("BRDC+OBS");
+            ctx.workspace.create_subdir("BRDC+OBS")?;
 
             let output = ctx.workspace.root.join("BRDC+OBS").join(&output_name);
 
@@ -70,6 +74,8 @@ pub fn dump_context_csv(
                 ProductType::BroadcastNavigation,
                 output.display()
             );
+
+            ctx.summary.output(&output);
         }
     }
 
@@ -167,8 +173,7 @@ fn write_joint_nav_obs_rinex(brdc: &Rinex, obs: &Rinex, path: &Path) -> Result<(
 }
 
 fn write_raw_nav_rinex(brdc: &Rinex, path: &Path) -> Result<(), Error> {
-    let mut fd = File::create(path)
-        .unwrap_or_else(|e| panic!("Failed to create file: {} - {}", path.display(), e));
+    let mut fd = File::create(path)?;
 
     let record = brdc.record.as_nav().unwrap();
 
@@ -1,34 +1,689 @@
 use clap::ArgMatches;
-use rinex::prelude::RinexType;
+use itertools::Itertools;
+use rinex::prelude::{
+    obs::LliFlags, Carrier, Constellation, Duration, Epoch, Observable, Rinex, RinexType, SV,
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
 
-use std::path::{Path, PathBuf};
+use gnss_qc::prelude::{html, Markup, QcExtraPage, Render};
 
 use crate::{
     cli::{Cli, Context},
     fops::{dump_rinex_auto_generated_name, parse_rinex},
-    preprocessing::rinex_preprocessing,
+    preprocessing::is_synthesized,
     Error,
 };
 
-/// Substract and format RINEX=RINEX(A)-RINEX(B)
-pub fn diff(ctx: &Context, cli: &Cli, submatches: &ArgMatches) -> Result<(), Error> {
+#[cfg(feature = "csv")]
+use csv::Writer;
+
+const SPEED_OF_LIGHT_M_S: f64 = 299_792_458.0;
+
+/// Maximal number of Allan deviation octaves
+const MAX_ADEV_OCTAVES: usize = 10;
+
+/// Pseudo range and phase samples of one receiver, in meters,
+/// with their loss of lock indication
+type Samples = BTreeMap<Epoch, HashMap<(SV, Observable), (f64, bool)>>;
+
+/// One differenced sample
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    t: Epoch,
+    /// Difference, in meters
+    value: f64,
+    /// Loss of lock of any differenced signal
+    lock_loss: bool,
+}
+
+/// Differenced time series, per SV and signal
+type Series = BTreeMap<(SV, Observable), Vec<Point>>;
+
+/// Continuous arc of a differenced signal: (epoch, raw, aligned) values in meters
+type Arc = Vec<(Epoch, f64, f64)>;
+
+/// Differenced signals, split into continuous arcs
+type Arcs = BTreeMap<(SV, Observable), Vec<Arc>>;
+
+/// Collects the pseudo range and phase samples of this Observation [Rinex].
+/// Phase observations are converted to meters, signal combinations already are.
+fn samples(rinex: &Rinex) -> Samples {
+    let mut samples = Samples::new();
+
+    for (k, v) in rinex.observations_iter() {
+        if !k.flag.is_ok() {
+            continue;
+        }
+
+        for signal in v.signals.iter() {
+            let value = if is_synthesized(&signal.observable)
+                || signal.observable.is_pseudorange_observable()
+            {
+                signal.value
+            } else if signal.observable.is_phase_observable() {
+                match Carrier::from_observable(signal.sv.constellation, &signal.observable) {
+                    Ok(carrier) => signal.value * SPEED_OF_LIGHT_M_S / carrier.frequency(),
+                    Err(_) => continue,
+                }
+            } else {
+                continue;
+            };
+
+            let lock_loss = signal
+                .lli
+                .map_or(false, |lli| lli.intersects(LliFlags::LOCK_LOSS));
+
+            samples
+                .entry(k.epoch)
+                .or_default()
+                .insert((signal.sv, signal.observable.clone()), (value, lock_loss));
+        }
+    }
+
+    samples
+}
+
+/// Selects one reference SV per constellation:
+/// the SV that was observed the most by both receivers
+fn reference_svs(a: &Samples, b: &Samples) -> BTreeMap<Constellation, SV> {
+    let mut counts = HashMap::<SV, usize>::new();
+
+    for (t, signals) in a.iter() {
+        if let Some(signals_b) = b.get(t) {
+            for (sv, observable) in signals.keys() {
+                if signals_b.contains_key(&(*sv, observable.clone())) {
+                    *counts.entry(*sv).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    counts
+        .into_iter()
+        .sorted()
+        .into_group_map_by(|(sv, _)| sv.constellation)
+        .into_iter()
+        .filter_map(|(constellation, counts)| {
+            counts
+                .into_iter()
+                .max_by_key(|(_, count)| *count)
+                .map(|(sv, _)| (constellation, sv))
+        })
+        .collect()
+}
+
+/// Between satellites single differences of one receiver: SV(t) - REF(t),
+/// for each signal, against the reference of that constellation.
+fn single_differences(samples: &Samples, references: &BTreeMap<Constellation, SV>) -> Series {
+    let mut series = Series::new();
+
+    for (t, signals) in samples.iter() {
+        for ((sv, observable), (value, lock_loss)) in signals.iter() {
+            let Some(reference) = references.get(&sv.constellation) else {
+                continue;
+            };
+
+            if sv == reference {
+                continue;
+            }
+
+            if let Some((value_ref, lock_loss_ref)) = signals.get(&(*reference, observable.clone()))
+            {
+                series
+                    .entry((*sv, observable.clone()))
+                    .or_default()
+                    .push(Point {
+                        t: *t,
+                        value: value - value_ref,
+                        lock_loss: *lock_loss || *lock_loss_ref,
+                    });
+            }
+        }
+    }
+
+    series
+}
+
+/// Receivers double differences: (B-A)(SV) - (B-A)(REF)
+fn double_differences(a: &Series, b: &Series) -> Series {
+    let mut series = Series::new();
+
+    for (key, points_b) in b.iter() {
+        let Some(points_a) = a.get(key) else {
+            continue;
+        };
+
+        let points_a = points_a
+            .iter()
+            .map(|point| (point.t, *point))
+            .collect::<HashMap<_, _>>();
+
+        let points = points_b
+            .iter()
+            .filter_map(|point_b| {
+                points_a.get(&point_b.t).map(|point_a| Point {
+                    t: point_b.t,
+                    value: point_b.value - point_a.value,
+                    lock_loss: point_a.lock_loss || point_b.lock_loss,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !points.is_empty() {
+            series.insert(key.clone(), points);
+        }
+    }
+
+    series
+}
+
+/// Splits each differenced signal into continuous arcs. A new arc starts on
+/// loss of lock, and after any gap larger than the sampling period
+/// (missing reference, missing epoch on either receiver..).
+/// Phase differences carry one ambiguity per arc: they are aligned
+/// to the first sample of each arc.
+fn arcs(series: &Series, sampling: Option<Duration>) -> Arcs {
+    let mut arcs = Arcs::new();
+
+    for ((sv, observable), points) in series.iter() {
+        let phase = observable.is_phase_observable();
+
+        let mut signal_arcs = Vec::<Arc>::new();
+        let mut prev_t = Option::<Epoch>::None;
+
+        for point in points.iter() {
+            let gap = match (prev_t, sampling) {
+                (Some(prev_t), Some(sampling)) => point.t - prev_t > sampling,
+                _ => false,
+            };
+
+            if prev_t.is_none() || gap || point.lock_loss {
+                signal_arcs.push(Arc::new());
+            }
+
+            // infaillible: an arc was just created
+            let arc = signal_arcs.last_mut().unwrap();
+
+            let aligned = match arc.first() {
+                Some((_, first, _)) if phase => point.value - first,
+                None if phase => 0.0,
+                _ => point.value,
+            };
+
+            arc.push((point.t, point.value, aligned));
+            prev_t = Some(point.t);
+        }
+
+        arcs.insert((*sv, observable.clone()), signal_arcs);
+    }
+
+    arcs
+}
+
+/// Overlapping Allan deviation at tau = m * tau_0, from these time deviations (in seconds).
+/// Each arc is sampled every tau_0, without gaps: the estimator is accumulated
+/// arc by arc, so no window spans a data gap.
+fn allan_deviation(arcs: &[Vec<f64>], tau_0: f64, m: usize) -> Option<f64> {
+    if m == 0 {
+        return None;
+    }
+
+    let (sum, terms) = arcs
+        .iter()
+        .filter(|x| x.len() > 2 * m)
+        .fold((0.0, 0), |(sum, terms), x| {
+            let n = x.len() - 2 * m;
+
+            let arc_sum = (0..n)
+                .map(|i| (x[i + 2 * m] - 2.0 * x[i + m] + x[i]).powi(2))
+                .sum::<f64>();
+
+            (sum + arc_sum, terms + n)
+        });
+
+    if terms == 0 {
+        return None;
+    }
+
+    let tau = m as f64 * tau_0;
+    Some((sum / (2.0 * tau.powi(2) * terms as f64)).sqrt())
+}
+
+/// Statistics of one differenced signal
+#[derive(Debug, Clone)]
+pub struct Statistics {
+    /// Differentiation: "SD(A)", "SD(B)" or "DD"
+    pub label: String,
+    pub sv: SV,
+    pub reference: SV,
+    pub observable: Observable,
+    pub samples: usize,
+    /// Number of continuous arcs
+    pub arcs: usize,
+    /// Mean of the (phase aligned) differences, in meters
+    pub mean_m: f64,
+    /// Standard deviation of the (phase aligned) differences, in meters
+    pub std_m: f64,
+    /// Allan deviations, per averaging time (in seconds)
+    pub adev: Vec<(f64, f64)>,
+}
+
+impl Statistics {
+    fn new(
+        label: &str,
+        sv: SV,
+        reference: SV,
+        observable: &Observable,
+        arcs: &[Arc],
+        tau_0: Option<f64>,
+    ) -> Self {
+        let values = arcs
+            .iter()
+            .flatten()
+            .map(|(_, _, aligned)| *aligned)
+            .collect::<Vec<_>>();
+
+        let samples = values.len();
+
+        let mean_m = values.iter().sum::<f64>() / samples as f64;
+
+        let std_m = (values
+            .iter()
+            .map(|value| (value - mean_m).powi(2))
+            .sum::<f64>()
+            / samples as f64)
+            .sqrt();
+
+        let mut adev = Vec::new();
+
+        if let Some(tau_0) = tau_0 {
+            // differences are expressed as time deviations
+            let x = arcs
+                .iter()
+                .map(|arc| {
+                    arc.iter()
+                        .map(|(_, _, aligned)| aligned / SPEED_OF_LIGHT_M_S)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            for octave in 0..MAX_ADEV_OCTAVES {
+                let m = 1 << octave;
+                match allan_deviation(&x, tau_0, m) {
+                    Some(value) => adev.push((m as f64 * tau_0, value)),
+                    None => break,
+                }
+            }
+        }
+
+        Self {
+            label: label.to_string(),
+            sv,
+            reference,
+            observable: observable.clone(),
+            samples,
+            arcs: arcs.len(),
+            mean_m,
+            std_m,
+            adev,
+        }
+    }
+}
+
+/// Differenced signals, as requested
+struct Differences {
+    /// Reference SV, per constellation
+    references: BTreeMap<Constellation, SV>,
+    /// Between satellites single differences, per receiver
+    single: Vec<(&'static str, Arcs)>,
+    /// Double differences
+    double: Option<Arcs>,
+    statistics: Vec<Statistics>,
+}
+
+impl Differences {
+    fn new(
+        a: &Rinex,
+        b: &Rinex,
+        reference: Option<SV>,
+        single: bool,
+        double: bool,
+    ) -> Result<Self, Error> {
+        let samples_a = samples(a);
+        let samples_b = samples(b);
+
+        let references = match reference {
+            Some(reference) => {
+                // other constellations cannot be differentiated
+                let dropped = samples_a
+                    .values()
+                    .chain(samples_b.values())
+                    .flat_map(|signals| signals.keys().map(|(sv, _)| sv.constellation))
+                    .filter(|constellation| *constellation != reference.constellation)
+                    .collect::<BTreeSet<_>>();
+
+                if !dropped.is_empty() {
+                    warn!(
+                        "diff: --ref-sv {} only applies to {}: {} not differentiated",
+                        reference,
+                        reference.constellation,
+                        dropped.iter().join(", ")
+                    );
+                }
+
+                BTreeMap::from([(reference.constellation, reference)])
+            },
+            None => reference_svs(&samples_a, &samples_b),
+        };
+
+        if references.is_empty() {
+            return Err(Error::InvalidInput(
+                "diff: no SV observed by both receivers".to_string(),
+            ));
+        }
+
+        for (constellation, reference) in references.iter() {
+            info!("diff: {} reference SV {}", constellation, reference);
+        }
+
+        let sampling = a.dominant_sampling_interval();
+
+        let tau_0 = sampling.map(|dt| dt.to_seconds()).filter(|dt| *dt > 0.0);
+
+        let sd_a = single_differences(&samples_a, &references);
+        let sd_b = single_differences(&samples_b, &references);
+
+        let mut statistics = Vec::new();
+
+        let mut collect = |label: &str, arcs: &Arcs| {
+            for ((sv, observable), arcs) in arcs.iter() {
+                // infaillible: differences only exist against a reference
+                let reference = references[&sv.constellation];
+
+                statistics.push(Statistics::new(
+                    label, *sv, reference, observable, arcs, tau_0,
+                ));
+            }
+        };
+
+        let double = if double {
+            let dd = arcs(&double_differences(&sd_a, &sd_b), sampling);
+            collect("DD", &dd);
+            Some(dd)
+        } else {
+            None
+        };
+
+        let single = if single {
+            let sd = vec![
+                ("SD(A)", arcs(&sd_a, sampling)),
+                ("SD(B)", arcs(&sd_b, sampling)),
+            ];
+
+            for (label, arcs) in sd.iter() {
+                collect(*label, arcs);
+            }
+
+            sd
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            references,
+            single,
+            double,
+            statistics,
+        })
+    }
+
+    /// Exports all differences and statistics as CSV, in the Workspace
+    #[cfg(feature = "csv")]
+    fn write_csv(&self, ctx: &Context) -> Result<(), Error> {
+        ctx.workspace.create_subdir("CSV")?;
+
+        if !self.single.is_empty() {
+            let path = ctx
+                .workspace
+                .root
+                .join("CSV")
+                .join("single_differences.csv");
+            let mut w = Writer::from_path(&path)?;
+            w.write_record([
+                "Epoch",
+                "Receiver",
+                "SV",
+                "Reference",
+                "Signal",
+                "Arc",
+                "Value [m]",
+                "Aligned [m]",
+            ])?;
+
+            for (label, arcs) in self.single.iter() {
+                let receiver = if *label == "SD(A)" { "A" } else { "B" };
+                for ((sv, observable), arcs) in arcs.iter() {
+                    for (index, arc) in arcs.iter().enumerate() {
+                        for (t, value, aligned) in arc.iter() {
+                            w.write_record(&[
+                                t.to_string(),
+                                receiver.to_string(),
+                                sv.to_string(),
+                                self.references[&sv.constellation].to_string(),
+                                observable.to_string(),
+                                index.to_string(),
+                                format!("{:.4}", value),
+                                format!("{:.4}", aligned),
+                            ])?;
+                        }
+                    }
+                }
+            }
+
+            w.flush()?;
+            info!("single differences dumped in {}", path.display());
+            ctx.summary.output(&path);
+        }
+
+        if let Some(arcs) = &self.double {
+            let path = ctx
+                .workspace
+                .root
+                .join("CSV")
+                .join("double_differences.csv");
+            let mut w = Writer::from_path(&path)?;
+            w.write_record([
+                "Epoch",
+                "SV",
+                "Reference",
+                "Signal",
+                "Arc",
+                "Value [m]",
+                "Aligned [m]",
+            ])?;
+
+            for ((sv, observable), arcs) in arcs.iter() {
+                for (index, arc) in arcs.iter().enumerate() {
+                    for (t, value, aligned) in arc.iter() {
+                        w.write_record(&[
+                            t.to_string(),
+                            sv.to_string(),
+                            self.references[&sv.constellation].to_string(),
+                            observable.to_string(),
+                            index.to_string(),
+                            format!("{:.4}", value),
+                            format!("{:.4}", aligned),
+                        ])?;
+                    }
+                }
+            }
+
+            w.flush()?;
+            info!("double differences dumped in {}", path.display());
+            ctx.summary.output(&path);
+        }
+
+        let path = ctx.workspace.root.join("CSV").join("diff_statistics.csv");
+        let mut w = Writer::from_path(&path)?;
+        w.write_record([
+            "Differences",
+            "SV",
+            "Reference",
+            "Signal",
+            "Samples",
+            "Arcs",
+            "Mean [m]",
+            "Std [m]",
+            "Tau [s]",
+            "ADEV",
+        ])?;
+
+        for stats in self.statistics.iter() {
+            let adev = if stats.adev.is_empty() {
+                vec![None]
+            } else {
+                stats.adev.iter().map(Some).collect()
+            };
+
+            for adev in adev {
+                w.write_record(&[
+                    stats.label.clone(),
+                    stats.sv.to_string(),
+                    stats.reference.to_string(),
+                    stats.observable.to_string(),
+                    stats.samples.to_string(),
+                    stats.arcs.to_string(),
+                    format!("{:.4}", stats.mean_m),
+                    format!("{:.4}", stats.std_m),
+                    adev.map_or(String::new(), |(tau, _)| format!("{}", tau)),
+                    adev.map_or(String::new(), |(_, adev)| format!("{:.3E}", adev)),
+                ])?;
+            }
+        }
+
+        w.flush()?;
+        info!("differences statistics dumped in {}", path.display());
+        ctx.summary.output(&path);
+        Ok(())
+    }
+
+    /// Exports all differences and statistics as CSV, in the Workspace
+    #[cfg(not(feature = "csv"))]
+    fn write_csv(&self, _: &Context) -> Result<(), Error> {
+        warn!("CSV export requires the \"csv\" feature");
+        Ok(())
+    }
+}
+
+struct ReportTab {}
+
+impl Render for ReportTab {
+    fn render(&self) -> Markup {
+        html! {
+            a id="menu:diff" {
+                span class="icon" {
+                    i class="fa-solid fa-code-compare" {}
+                }
+                "Differences"
+            }
+        }
+    }
+}
+
+/// Differences statistics report
+struct ReportContent {
+    references: BTreeMap<Constellation, SV>,
+    statistics: Vec<Statistics>,
+}
+
+impl Render for ReportContent {
+    fn render(&self) -> Markup {
+        html! {
+            div class="table-container" {
+                table class="table is-bordered" {
+                    tbody {
+                        tr {
+                            th class="is-info" {
+                                "Reference SV"
+                            }
+                            td {
+                                (self.references.values().join(", "))
+                            }
+                        }
+                    }
+                }
+                table class="table is-bordered" {
+                    thead {
+                        tr {
+                            th { "Differences" }
+                            th { "SV" }
+                            th { "Signal" }
+                            th { "Samples" }
+                            th { "Arcs" }
+                            th { "Mean [m]" }
+                            th { "Std [m]" }
+                            th { "ADEV" }
+                        }
+                    }
+                    tbody {
+                        @for stats in self.statistics.iter() {
+                            tr {
+                                td { (stats.label) }
+                                td { (stats.sv.to_string()) }
+                                td { (stats.observable.to_string()) }
+                                td { (stats.samples) }
+                                td { (stats.arcs) }
+                                td { (format!("{:.4}", stats.mean_m)) }
+                                td { (format!("{:.4}", stats.std_m)) }
+                                td {
+                                    (stats.adev
+                                        .iter()
+                                        .map(|(tau, adev)| format!("{}s: {:.3E}", tau, adev))
+                                        .join(", "))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Substract and format RINEX=RINEX(B)-RINEX(A).
+/// Between satellites single differences and double differences are
+/// computed on request: they are exported as CSV, and their statistics
+/// are returned as a report page.
+pub fn diff(
+    ctx: &Context,
+    cli: &Cli,
+    submatches: &ArgMatches,
+) -> Result<Option<QcExtraPage>, Error> {
     let ctx_data = &ctx.data;
 
     let rinex_a = ctx_data
         .observation()
-        .expect("RINEX (A) - (B) requires OBS RINEX files");
+        .ok_or(Error::MissingObservationRinex)?;
 
     let gzip = submatches.get_flag("gzip");
     let forced_short_v2 = submatches.get_flag("short");
 
+    let single = submatches.get_flag("sd");
+    let double = submatches.get_flag("dd");
+    let reference = submatches.get_one::<SV>("ref-sv").copied();
+
     let path_b = submatches.get_one::<PathBuf>("file").unwrap();
-    let mut rinex_b = parse_rinex(&path_b);
+    let mut rinex_b = parse_rinex(&path_b)?;
 
-    assert_eq!(
-        rinex_b.header.rinex_type,
-        RinexType::ObservationData,
-        "only applies to Observation RINEX!"
-    );
+    if rinex_b.header.rinex_type != RinexType::ObservationData {
+        return Err(Error::InvalidInput(
+            "diff only applies to Observation RINEX".to_string(),
+        ));
+    }
 
     if cli.matches.get_flag("rnx2crx") {
         rinex_b.rnx2crnx_mut();
@@ -38,17 +693,30 @@ pub fn diff(ctx: &Context, cli: &Cli, submatches: &ArgMatches) -> Result<(), Err
         rinex_b.crnx2rnx_mut();
     }
 
-    rinex_preprocessing(&mut rinex_b, &cli);
+    ctx.preprocessing.apply_rinex(&mut rinex_b);
 
     let rinex_c = rinex_b
         .observations_substract(&rinex_a)
-        .unwrap_or_else(|e| {
-            panic!("diff failed with: {:?}", e);
-        });
+        .map_err(|e| Error::InvalidInput(format!("diff failed with: {:?}", e)))?;
 
     let input_name = rinex_a.standard_filename(forced_short_v2, None, None);
     let input_path = Path::new(&input_name);
-    dump_rinex_auto_generated_name(&ctx, input_path, &rinex_c, gzip, None);
+    dump_rinex_auto_generated_name(&ctx, input_path, &rinex_c, gzip, None)?;
+
+    if !single && !double {
+        return Ok(None);
+    }
+
+    let differences = Differences::new(rinex_a, &rinex_b, reference, single, double)?;
+
+    differences.write_csv(ctx)?;
 
-    Ok(())
+    Ok(Some(QcExtraPage {
+        tab: Box::new(ReportTab {}),
+        html_id: "diff".to_string(),
+        content: Box::new(ReportContent {
+            references: differences.references,
+            statistics: differences.statistics,
+        }),
+    }))
 }
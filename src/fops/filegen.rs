@@ -6,7 +6,7 @@ use crate::fops::csv::dump_context_csv;
 
 use crate::{
     cli::Context,
-    fops::{custom_prod_attributes, output_filename},
+    fops::{custom_prod_attributes, output_filename, write_rinex},
     Error,
 };
 
@@ -21,7 +21,9 @@ pub fn filegen(ctx: &Context, matches: &ArgMatches, submatches: &ArgMatches) ->
 
     #[cfg(not(feature = "csv"))]
     if submatches.get_flag("csv") {
-        panic!("Not available. Requires `csv` compilation flag.");
+        return Err(Error::Configuration(
+            "--csv not available: compile with csv option".to_string(),
+        ));
     }
 
     write(ctx, matches, submatches)?;
@@ -41,7 +43,7 @@ fn write(ctx: &Context, matches: &ArgMatches, submatches: &ArgMatches) -> Result
         (ProductType::ANTEX, "ANTEX"),
     ] {
         if let Some(rinex) = ctx_data.rinex(product) {
-            ctx.workspace.create_subdir(dir);
+            ctx.workspace.create_subdir(dir)?;
             let prod = custom_prod_attributes(rinex, submatches);
             let filename = output_filename(rinex, matches, submatches, prod);
 
@@ -53,15 +55,7 @@ fn write(ctx: &Context, matches: &ArgMatches, submatches: &ArgMatches) -> Result
                 .to_string_lossy()
                 .to_string();
 
-            if submatches.get_flag("gzip") {
-                rinex.to_gzip_file(&output_path).unwrap_or_else(|_| {
-                    panic!("failed to generate {} RINEX \"{}\"", product, output_path)
-                });
-            } else {
-                rinex.to_file(&output_path).unwrap_or_else(|_| {
-                    panic!("failed to generate {} RINEX \"{}\"", product, output_path)
-                });
-            }
+            write_rinex(ctx, rinex, &output_path, submatches.get_flag("gzip"))?;
 
             info!("{} RINEX \"{}\" has been generated", product, output_path);
         }
@@ -1,90 +1,401 @@
 use clap::ArgMatches;
-use std::path::{Path, PathBuf};
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use rinex::prelude::{qc::Merge, RinexType};
+use gnss_qc::prelude::{MaskOperand, Preprocessing, SP3};
+use rinex::prelude::{qc::Merge, Duration, Epoch, Rinex, RinexType};
 
 use crate::{
     cli::{Cli, Context},
-    fops::{dump_rinex_auto_generated_name, parse_rinex},
-    preprocessing::rinex_preprocessing,
+    fops::{dump_rinex_auto_generated_name, dump_sp3_auto_generated_name, epoch_filter},
+    loader::{self, Product},
     Error,
 };
 
-/// Merge single file into [Context], dump into workspace.
-pub fn merge(ctx: &Context, cli: &Cli, submatches: &ArgMatches) -> Result<(), Error> {
-    let ctx_data = &ctx.data;
+/// What to do with epochs described by several products
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OverlapPolicy {
+    /// Combine the overlapping records
+    #[default]
+    Union,
+    /// Keep the epochs of the earliest product
+    First,
+    /// Keep the epochs of the latest product
+    Last,
+    /// Overlapping products are not merged
+    Abort,
+}
+
+impl FromStr for OverlapPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "union" => Ok(Self::Union),
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            "abort" => Ok(Self::Abort),
+            _ => Err(format!("unknown overlap policy \"{}\"", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for OverlapPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Union => write!(f, "union"),
+            Self::First => write!(f, "first"),
+            Self::Last => write!(f, "last"),
+            Self::Abort => write!(f, "abort"),
+        }
+    }
+}
+
+/// Products that we know how to fold together
+trait Foldable: Merge + Preprocessing + Sized {
+    /// First and last [Epoch]
+    fn time_frame(&self) -> Option<(Epoch, Epoch)>;
+
+    /// Sampling period
+    fn sampling(&self) -> Option<Duration>;
+
+    /// Describes all header incompatibilities with `rhs`
+    fn conflicts(&self, rhs: &Self) -> Vec<String>;
+}
+
+/// Describes a conflict, when both values differ
+fn conflict<T: PartialEq + std::fmt::Debug>(
+    conflicts: &mut Vec<String>,
+    field: &str,
+    lhs: T,
+    rhs: T,
+) {
+    if lhs != rhs {
+        conflicts.push(format!("{} {:?} differs from {:?}", field, rhs, lhs));
+    }
+}
+
+impl Foldable for Rinex {
+    fn time_frame(&self) -> Option<(Epoch, Epoch)> {
+        self.first_epoch().zip(self.last_epoch())
+    }
+
+    fn sampling(&self) -> Option<Duration> {
+        self.dominant_sampling_interval()
+    }
+
+    fn conflicts(&self, rhs: &Self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        // Observation and Meteo are tied to one station
+        if self.is_observation_rinex() || self.is_meteo_rinex() {
+            let marker = |rinex: &Rinex| {
+                rinex
+                    .header
+                    .geodetic_marker
+                    .as_ref()
+                    .map(|marker| marker.name.trim().to_uppercase())
+            };
+
+            conflict(&mut conflicts, "marker", marker(self), marker(rhs));
+
+            let receiver = |rinex: &Rinex| {
+                rinex
+                    .header
+                    .rcvr
+                    .as_ref()
+                    .map(|rcvr| format!("{} {}", rcvr.model.trim(), rcvr.sn.trim()))
+            };
+
+            conflict(&mut conflicts, "receiver", receiver(self), receiver(rhs));
+        }
+
+        if self.is_observation_rinex() {
+            let observables = |rinex: &Rinex| {
+                rinex
+                    .header
+                    .obs
+                    .as_ref()
+                    .map(|obs| {
+                        obs.codes
+                            .iter()
+                            .map(|(constellation, codes)| {
+                                (
+                                    constellation.to_string(),
+                                    codes.iter().map(|code| code.to_string()).sorted().join(","),
+                                )
+                            })
+                            .collect::<BTreeMap<_, _>>()
+                    })
+                    .unwrap_or_default()
+            };
+
+            conflict(
+                &mut conflicts,
+                "observables",
+                observables(self),
+                observables(rhs),
+            );
+        }
+
+        if self.is_observation_rinex() || self.is_meteo_rinex() || self.is_clock_rinex() {
+            conflict(&mut conflicts, "sampling", self.sampling(), rhs.sampling());
+        }
+
+        conflicts
+    }
+}
+
+impl Foldable for SP3 {
+    fn time_frame(&self) -> Option<(Epoch, Epoch)> {
+        self.first_epoch().zip(self.last_epoch())
+    }
+
+    fn sampling(&self) -> Option<Duration> {
+        Some(self.header.epoch_interval)
+    }
+
+    fn conflicts(&self, rhs: &Self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+
+        conflict(
+            &mut conflicts,
+            "agency",
+            self.header.agency.trim(),
+            rhs.header.agency.trim(),
+        );
+
+        conflict(
+            &mut conflicts,
+            "coordinates system",
+            self.header.coord_system.trim(),
+            rhs.header.coord_system.trim(),
+        );
 
+        conflict(
+            &mut conflicts,
+            "timescale",
+            self.header.timescale,
+            rhs.header.timescale,
+        );
+
+        conflict(&mut conflicts, "sampling", self.sampling(), rhs.sampling());
+
+        conflicts
+    }
+}
+
+/// Folds all products of the same kind into a single product.
+/// Products are merged in chronological order. Header conflicts are reported,
+/// and abort the operation in `strict` mode. Overlapping epochs are handled
+/// according to the [OverlapPolicy].
+fn fold<T: Foldable>(
+    products: Vec<(PathBuf, T)>,
+    policy: OverlapPolicy,
+    strict: bool,
+) -> Result<Option<T>, Error> {
+    // chronological order. Empty products do not contribute.
+    let mut products = products
+        .into_iter()
+        .filter_map(|(path, product)| match product.time_frame() {
+            Some(time_frame) => Some((path, product, time_frame)),
+            None => {
+                warn!("\"{}\" does not contain any epoch", path.display());
+                None
+            },
+        })
+        .collect::<Vec<_>>();
+
+    products.sort_by_key(|(_, _, (first, _))| *first);
+
+    let mut products = products.into_iter();
+
+    let Some((first_path, mut folded, (_, mut last))) = products.next() else {
+        return Ok(None);
+    };
+
+    for (path, mut product, (next_first, next_last)) in products {
+        let conflicts = folded.conflicts(&product);
+
+        for conflict in conflicts.iter() {
+            warn!(
+                "\"{}\" is not compatible with \"{}\": {}",
+                path.display(),
+                first_path.display(),
+                conflict
+            );
+        }
+
+        if strict && !conflicts.is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "\"{}\" is not compatible with \"{}\" ({} conflict(s))",
+                path.display(),
+                first_path.display(),
+                conflicts.len()
+            )));
+        }
+
+        if next_first <= last {
+            warn!(
+                "\"{}\" overlaps previous product(s) from {} to {} ({} policy)",
+                path.display(),
+                next_first,
+                last.min(next_last),
+                policy
+            );
+
+            match policy {
+                OverlapPolicy::Union => {},
+                OverlapPolicy::Abort => {
+                    return Err(Error::InvalidInput(format!(
+                        "\"{}\" overlaps previous product(s)",
+                        path.display()
+                    )));
+                },
+                OverlapPolicy::First => {
+                    let after = epoch_filter(MaskOperand::GreaterThan, last);
+                    product = product.filter(&after);
+                },
+                OverlapPolicy::Last => {
+                    let before = epoch_filter(MaskOperand::LowerThan, next_first);
+                    let after = epoch_filter(MaskOperand::GreaterThan, next_last);
+                    folded = folded.filter(&before).merge(&folded.filter(&after))?;
+                },
+            }
+        } else if let Some(sampling) = folded.sampling() {
+            if next_first - last > sampling {
+                warn!(
+                    "\"{}\" is not consecutive ({} data gap): merged product is not continuous",
+                    path.display(),
+                    next_first - last
+                );
+            }
+        }
+
+        debug!("merging \"{}\"", path.display());
+        folded = folded.merge(&product)?;
+        last = last.max(next_last);
+    }
+
+    Ok(Some(folded))
+}
+
+/// Folds every loaded file, and possible extra files, into one product per
+/// kind (RINEX type, SP3). Dumps the results into workspace.
+pub fn merge(ctx: &Context, cli: &Cli, submatches: &ArgMatches) -> Result<(), Error> {
     // options
     let gzip = submatches.get_flag("gzip");
     let short_v2_name = submatches.get_flag("short");
-    let merge_path = submatches.get_one::<PathBuf>("file").unwrap();
+    let strict = submatches.get_flag("strict");
+
+    let policy = submatches
+        .get_one::<OverlapPolicy>("overlap")
+        .copied()
+        .unwrap_or_default();
 
     let forced_rinex = cli.matches.get_flag("crx2rnx");
     let forced_crinex = cli.matches.get_flag("rnx2crx");
 
-    let mut rinex_b = parse_rinex(&merge_path);
+    // loaded files are parsed again, one by one, so they can be checked individually
+    let mut paths = ctx.summary.inputs();
+
+    if let Some(extra) = submatches.get_many::<PathBuf>("file") {
+        paths.extend(extra.cloned());
+    }
+
+    // a file may be loaded and passed to merge as well: it is only folded once
+    let mut unique = HashSet::<PathBuf>::new();
+
+    paths.retain(|path| {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let retained = unique.insert(canonical);
+        if !retained {
+            debug!("\"{}\" is already loaded", path.display());
+        }
+        retained
+    });
+
+    let mut rinex_products = BTreeMap::<String, (RinexType, Vec<(PathBuf, Rinex)>)>::new();
+    let mut sp3_products = Vec::<(PathBuf, SP3)>::new();
+
+    for path in paths.iter() {
+        let parsed = loader::parse_file(path)
+            .map_err(|e| Error::InputLoading(path.display().to_string(), e))?;
+
+        match parsed {
+            (_, Product::Rinex(mut rinex)) => {
+                ctx.preprocessing.apply_rinex(&mut rinex);
+
+                if forced_rinex {
+                    rinex.crnx2rnx_mut();
+                }
 
-    rinex_preprocessing(&mut rinex_b, cli);
+                if forced_crinex {
+                    rinex.rnx2crnx_mut();
+                }
 
-    if forced_rinex {
-        rinex_b.crnx2rnx_mut();
+                let rinex_type = rinex.header.rinex_type;
+
+                rinex_products
+                    .entry(rinex_type.to_string())
+                    .or_insert_with(|| (rinex_type, Vec::new()))
+                    .1
+                    .push((path.to_path_buf(), rinex));
+            },
+            (_, Product::SP3(mut sp3)) => {
+                ctx.preprocessing.apply_sp3(&mut sp3);
+                sp3_products.push((path.to_path_buf(), sp3));
+            },
+        }
     }
 
-    if forced_crinex {
-        rinex_b.rnx2crnx_mut();
+    let mut merged = 0;
+
+    for (rinex_type, products) in rinex_products.into_values() {
+        match rinex_type {
+            RinexType::ObservationData
+            | RinexType::NavigationData
+            | RinexType::MeteoData
+            | RinexType::IonosphereMaps
+            | RinexType::ClockData => {},
+            rinex_format => {
+                warn!("merge is not available for {}", rinex_format);
+                continue;
+            },
+        }
+
+        if products.len() < 2 {
+            debug!("{}: single file, nothing to merge", rinex_type);
+            continue;
+        }
+
+        info!("merging {} {} files", products.len(), rinex_type);
+
+        if let Some(folded) = fold(products, policy, strict)? {
+            let standard_name = folded.standard_filename(short_v2_name, None, None);
+            let input_path = Path::new(&standard_name);
+            dump_rinex_auto_generated_name(ctx, input_path, &folded, gzip, None)?;
+            merged += 1;
+        }
     }
 
-    // perform merge
-    let (origin_name, rinex_c) = match rinex_b.header.rinex_type {
-        RinexType::ObservationData => {
-            let rinex_a = ctx_data
-                .observation()
-                .ok_or(Error::MissingObservationRinex)?;
-
-            (
-                rinex_a.standard_filename(short_v2_name, None, None),
-                rinex_a.merge(&rinex_b)?,
-            )
-        },
-        RinexType::NavigationData => {
-            let rinex_a = ctx_data
-                .brdc_navigation()
-                .ok_or(Error::MissingNavigationRinex)?;
-
-            (
-                rinex_a.standard_filename(short_v2_name, None, None),
-                rinex_a.merge(&rinex_b)?,
-            )
-        },
-        RinexType::MeteoData => {
-            let rinex_a = ctx_data.meteo().ok_or(Error::MissingMeteoRinex)?;
-
-            (
-                rinex_a.standard_filename(short_v2_name, None, None),
-                rinex_a.merge(&rinex_b)?,
-            )
-        },
-        RinexType::IonosphereMaps => {
-            let rinex_a = ctx_data.ionex().ok_or(Error::MissingIONEX)?;
-
-            (
-                rinex_a.standard_filename(short_v2_name, None, None),
-                rinex_a.merge(&rinex_b)?,
-            )
-        },
-        RinexType::ClockData => {
-            let rinex_a = ctx_data.clock().ok_or(Error::MissingClockRinex)?;
-
-            (
-                rinex_a.standard_filename(short_v2_name, None, None),
-                rinex_a.merge(&rinex_b)?,
-            )
-        },
-        rinex_format => panic!("merge is not available for {}", rinex_format),
-    };
+    if sp3_products.len() > 1 {
+        info!("merging {} SP3 files", sp3_products.len());
+
+        if let Some(folded) = fold(sp3_products, policy, strict)? {
+            dump_sp3_auto_generated_name(ctx, &folded, submatches, None)?;
+            merged += 1;
+        }
+    }
 
-    let input_path = Path::new(&origin_name);
-    dump_rinex_auto_generated_name(&ctx, &input_path, &rinex_c, gzip, None);
+    if merged == 0 {
+        return Err(Error::InvalidInput(
+            "merge requires at least two files of the same kind".to_string(),
+        ));
+    }
 
     Ok(())
 }
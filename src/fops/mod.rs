@@ -11,37 +11,56 @@ pub mod csv;
 pub use cbin::constell_timescale_binning;
 pub use diff::diff;
 pub use filegen::filegen;
-pub use merge::merge;
+pub use merge::{merge, OverlapPolicy};
 pub use split::split;
 pub use tbin::time_binning;
 
-use std::path::Path;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
 
 use clap::ArgMatches;
+use flate2::{write::GzEncoder, Compression};
+
+use gnss_qc::prelude::{Filter, FilterItem, MaskOperand, TimeScale, SP3};
 
 use rinex::{
-    prelude::Rinex,
+    prelude::{Constellation, Duration, Epoch, Rinex},
     prod::{DataSource, DetailedProductionAttributes, ProductionAttributes, FFU, PPU},
 };
 
-use crate::Context;
+use crate::{loader, preprocessing::without_synthesized, Context, Error};
 
 /// Shared method to parse a RINEX file
-pub fn parse_rinex(path: &Path) -> Rinex {
-    let extension = path
-        .extension()
-        .unwrap_or_else(|| panic!("failed to determine file extension: {}", path.display()))
-        .to_string_lossy()
-        .to_string();
+pub fn parse_rinex(path: &Path) -> Result<Rinex, Error> {
+    loader::parse_rinex(path).map_err(|e| Error::InputLoading(path.display().to_string(), e))
+}
+
+/// Shared method to format a RINEX file, possibly gzip compressed.
+/// Signal combinations are not valid RINEX observables: they are not formatted.
+fn write_rinex(ctx: &Context, rinex: &Rinex, output_path: &str, gzip: bool) -> Result<(), Error> {
+    let stripped = without_synthesized(rinex);
 
-    let rinex = if extension == "gz" {
-        Rinex::from_gzip_file(&path)
-            .unwrap_or_else(|e| panic!("Failed to parse gzip compressed RINEX: {}", e))
+    if stripped.is_some() {
+        warn!(
+            "\"{}\": signal combinations are not formatted in RINEX (use --csv)",
+            output_path
+        );
+    }
+
+    let rinex = stripped.as_ref().unwrap_or(rinex);
+
+    let formatted = if gzip {
+        rinex.to_gzip_file(output_path)
     } else {
-        Rinex::from_file(&path).unwrap_or_else(|e| panic!("Failed to parse RINEX: {}", e))
+        rinex.to_file(output_path)
     };
 
-    rinex
+    formatted.map_err(|e| Error::OutputFormatting(output_path.to_string(), e))?;
+    ctx.summary.output(output_path);
+    Ok(())
 }
 
 /// Shared method to dump a RINEX file into the workspace
@@ -51,10 +70,13 @@ pub fn dump_rinex_auto_generated_name(
     rinex: &Rinex,
     gzip: bool,
     custom_subdir: Option<String>,
-) {
+) -> Result<(), Error> {
     let suffix = input_path
         .file_name()
-        .expect("failed to determine output filename")
+        .ok_or(Error::InvalidInput(format!(
+            "failed to determine output filename from \"{}\"",
+            input_path.display()
+        )))?
         .to_string_lossy()
         .to_string();
 
@@ -72,17 +94,256 @@ pub fn dump_rinex_auto_generated_name(
         output_path.push_str(".gz");
     }
 
+    write_rinex(ctx, rinex, &output_path, gzip)?;
+
+    info!("\"{}\" has been generated", output_path);
+    Ok(())
+}
+
+/// Shared method to format a SP3 file, possibly gzip compressed
+fn write_sp3(ctx: &Context, sp3: &SP3, output_path: &str, gzip: bool) -> Result<(), Error> {
+    let fd = File::create(output_path)?;
+
+    let formatting_error = |e: String| Error::Sp3Formatting(output_path.to_string(), e);
+
     if gzip {
-        rinex
-            .to_gzip_file(&output_path)
-            .unwrap_or_else(|e| panic!("Failed to format {}: {}", output_path, e));
+        let mut writer = BufWriter::new(GzEncoder::new(fd, Compression::default()));
+        sp3.format(&mut writer)
+            .map_err(|e| formatting_error(e.to_string()))?;
+
+        let encoder = writer.into_inner().map_err(|e| e.into_error())?;
+        encoder.finish()?;
+    } else {
+        let mut writer = BufWriter::new(fd);
+        sp3.format(&mut writer)
+            .map_err(|e| formatting_error(e.to_string()))?;
+
+        writer.flush()?;
+    }
+
+    ctx.summary.output(output_path);
+    Ok(())
+}
+
+/// Time mask, described without string round trip
+fn epoch_filter(operand: MaskOperand, t: Epoch) -> Filter {
+    Filter::mask(operand, FilterItem::EpochItem(t))
+}
+
+/// Formats a period as used in standard file names: "30S", "15M", "01D"..
+fn period_code(dt: Duration) -> String {
+    let seconds = dt.to_seconds().round() as u64;
+
+    if seconds >= 86_400 && seconds % 86_400 == 0 {
+        format!("{:02}D", seconds / 86_400)
+    } else if seconds >= 3_600 && seconds % 3_600 == 0 {
+        format!("{:02}H", seconds / 3_600)
+    } else if seconds >= 60 && seconds % 60 == 0 {
+        format!("{:02}M", seconds / 60)
     } else {
-        rinex
-            .to_file(&output_path)
-            .unwrap_or_else(|e| panic!("Failed to format {}: {}", output_path, e));
+        format!("{:02}S", seconds)
+    }
+}
+
+/// Nominal duration of this production periodicity
+fn ppu_duration(ppu: PPU) -> Option<Duration> {
+    match ppu {
+        PPU::QuarterHour => Some(Duration::from_seconds(900.0)),
+        PPU::Hourly => Some(Duration::from_hours(1.0)),
+        PPU::Daily => Some(Duration::from_days(1.0)),
+        PPU::Yearly => Some(Duration::from_days(365.0)),
+        _ => None,
+    }
+}
+
+/// Shortest standard production periodicity that covers this duration
+fn covering_ppu(duration: Duration) -> Option<PPU> {
+    [PPU::QuarterHour, PPU::Hourly, PPU::Daily, PPU::Yearly]
+        .into_iter()
+        .find(|ppu| ppu_duration(*ppu).map_or(false, |dt| duration <= dt))
+}
+
+/// V2 short file names describe hourly sessions with a letter,
+/// from 'a' (00:00) to 'x' (23:00), instead of the daily '0'.
+fn hourly_session_name(filename: &str, start: Epoch) -> String {
+    // "ssssdddh.yyt"
+    if filename.as_bytes().get(8) != Some(&b'.') {
+        return filename.to_string();
+    }
+
+    let (_, _, _, hours, _, _, _) = start.to_gregorian(start.time_scale);
+    let letter = (b'a' + hours) as char;
+
+    filename
+        .char_indices()
+        .map(|(i, c)| if i == 7 { letter } else { c })
+        .collect()
+}
+
+/// Campaign (project) and solution type of the first SP3 input
+/// that follows modern naming conventions,
+/// like ("MGX", "FIN") for "GRG0MGXFIN_20201770000_01D_15M_ORB.SP3".
+/// They only describe this [SP3] if it covers the same constellation(s)
+/// as the input products: a product binned per constellation is no longer "MGX".
+fn sp3_input_campaign(ctx: &Context, sp3: &SP3) -> Option<(String, String)> {
+    let input = ctx.data.sp3()?;
+
+    if input.header.constellation != sp3.header.constellation {
+        return None;
+    }
+
+    ctx.summary.inputs().iter().find_map(|path| {
+        let name = path.file_name()?.to_string_lossy().to_uppercase();
+
+        if name.as_bytes().get(10) != Some(&b'_') || !name.contains("_ORB.SP3") {
+            return None;
+        }
+
+        Some((name.get(4..7)?.to_string(), name.get(7..10)?.to_string()))
+    })
+}
+
+/// Returns the standard file name of this [SP3].
+/// Modern (long) names look like "GRG0MGXFIN_20201770000_01D_15M_ORB.SP3",
+/// where the length covers the entire product (multi day products included).
+/// Campaign and solution type are carried over from the SP3 input name,
+/// when it follows modern conventions and covers the same constellation(s).
+/// Otherwise, they cannot be determined
+/// from the file content: we then assume a final ("FIN") solution,
+/// from the "MGX" campaign for Mixed products, "OPS" otherwise.
+/// Short (deprecated) names look like "grg21114.sp3".
+/// Both names describe the first epoch in GPST, whatever the product timescale.
+/// Agency is taken from the header, unless `custom_agency` is defined.
+pub fn sp3_standard_filename(
+    ctx: &Context,
+    sp3: &SP3,
+    short: bool,
+    custom_agency: Option<&String>,
+) -> Result<String, Error> {
+    let (first, last) = match (sp3.first_epoch(), sp3.last_epoch()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            return Err(Error::InvalidInput(
+                "SP3 does not contain any epoch".to_string(),
+            ));
+        },
+    };
+
+    let agency = custom_agency.unwrap_or(&sp3.header.agency);
+
+    let mut agency = agency
+        .trim()
+        .chars()
+        .take(3)
+        .collect::<String>()
+        .to_uppercase();
+
+    while agency.len() < 3 {
+        agency.push('X');
+    }
+
+    let first = first.to_time_scale(TimeScale::GPST);
+
+    if short {
+        let (week, nanos) = first.to_time_of_week();
+        let day_of_week = nanos / (86_400 * 1_000_000_000);
+
+        return Ok(format!(
+            "{}{:04}{}.sp3",
+            agency.to_lowercase(),
+            week,
+            day_of_week
+        ));
+    }
+
+    let (project, solution) = sp3_input_campaign(ctx, sp3).unwrap_or_else(|| {
+        let project = if sp3.header.constellation == Constellation::Mixed {
+            "MGX"
+        } else {
+            "OPS"
+        };
+        (project.to_string(), "FIN".to_string())
+    });
+
+    let (year, _, _, hours, minutes, _, _) = first.to_gregorian(TimeScale::GPST);
+
+    let new_year = Epoch::from_gregorian_at_midnight(year, 1, 1, TimeScale::GPST);
+    let day_of_year = ((first - new_year).to_seconds() / 86_400.0).floor() as u32 + 1;
+
+    let sampling = sp3.header.epoch_interval;
+    let length = last - first + sampling;
+
+    Ok(format!(
+        "{}0{}{}_{:04}{:03}{:02}{:02}_{}_{}_ORB.SP3",
+        agency,
+        project,
+        solution,
+        year,
+        day_of_year,
+        hours,
+        minutes,
+        period_code(length),
+        period_code(sampling),
+    ))
+}
+
+/// Returns SP3 output filename to be generated.
+/// Prefers manual user input, otherwise uses standard naming conventions.
+fn sp3_output_filename(
+    ctx: &Context,
+    sp3: &SP3,
+    matches: &ArgMatches,
+    submatches: &ArgMatches,
+) -> Result<String, Error> {
+    let mut filename = match matches.get_one::<String>("output-name") {
+        Some(custom) => custom.to_string(),
+        None => sp3_standard_filename(
+            ctx,
+            sp3,
+            submatches.get_flag("short"),
+            submatches.get_one::<String>("agency"),
+        )?,
+    };
+
+    if submatches.get_flag("gzip") {
+        filename.push_str(".gz");
+    }
+
+    Ok(filename)
+}
+
+/// Shared method to dump a SP3 file into the workspace, using standard naming conventions
+pub fn dump_sp3_auto_generated_name(
+    ctx: &Context,
+    sp3: &SP3,
+    submatches: &ArgMatches,
+    custom_subdir: Option<String>,
+) -> Result<(), Error> {
+    let filename = sp3_standard_filename(
+        ctx,
+        sp3,
+        submatches.get_flag("short"),
+        submatches.get_one::<String>("agency"),
+    )?;
+
+    let gzip = submatches.get_flag("gzip");
+
+    let mut output_path = ctx.workspace.root.clone();
+
+    if let Some(subdir) = custom_subdir {
+        output_path = output_path.join(subdir);
     }
 
+    let mut output_path = output_path.join(filename).to_string_lossy().to_string();
+
+    if gzip {
+        output_path.push_str(".gz");
+    }
+
+    write_sp3(ctx, sp3, &output_path, gzip)?;
+
     info!("\"{}\" has been generated", output_path);
+    Ok(())
 }
 
 /*
@@ -1,20 +1,121 @@
 use clap::ArgMatches;
-use gnss_qc::prelude::ProductType;
-use rinex::prelude::{processing::Split, Epoch};
-use std::path::Path;
+use gnss_qc::prelude::{Filter, MaskOperand, Preprocessing, ProductType};
+use rinex::{
+    prelude::{Duration, Epoch},
+    prod::PPU,
+};
+use std::collections::{BTreeSet, HashSet};
 
-use crate::{cli::Context, fops::dump_rinex_auto_generated_name, Error};
+use crate::{
+    cli::Context,
+    fops::{
+        covering_ppu, custom_prod_attributes, epoch_filter, hourly_session_name,
+        sp3_standard_filename, write_rinex, write_sp3,
+    },
+    Error,
+};
 
-/// Splits input files at specified Time Instant
+/// Cut points of this (sorted) time serie: the requested instants
+/// within the time frame, and the data gaps larger than `gap`.
+fn cut_points(epochs: &[Epoch], instants: &[Epoch], gap: Option<Duration>) -> Vec<Epoch> {
+    let (first, last) = match (epochs.first(), epochs.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Vec::new(),
+    };
+
+    let mut cuts = instants
+        .iter()
+        .filter(|t| **t > first && **t <= last)
+        .copied()
+        .collect::<BTreeSet<_>>();
+
+    if let Some(gap) = gap {
+        for pair in epochs.windows(2) {
+            if pair[1] - pair[0] > gap {
+                debug!("data gap from {} to {}", pair[0], pair[1]);
+                cuts.insert(pair[1]);
+            }
+        }
+    }
+
+    cuts.into_iter().collect()
+}
+
+/// Segments [start, end) delimited by these cut points.
+/// First and last segments are open.
+fn segments(cuts: &[Epoch]) -> Vec<(Option<Epoch>, Option<Epoch>)> {
+    let mut bounds = vec![None];
+    bounds.extend(cuts.iter().map(|t| Some(*t)));
+    bounds.push(None);
+
+    bounds.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// Filters that retain this segment
+fn segment_filters(start: Option<Epoch>, end: Option<Epoch>) -> Vec<Filter> {
+    let mut filters = Vec::new();
+
+    if let Some(start) = start {
+        filters.push(epoch_filter(MaskOperand::GreaterEquals, start));
+    }
+
+    if let Some(end) = end {
+        filters.push(epoch_filter(MaskOperand::LowerThan, end));
+    }
+
+    filters
+}
+
+/// Returns a unique file name. Standard names describe each segment (start time,
+/// period, V2 session letter), but may still collide (two segments within the same hour
+/// in V2 short names, short SP3 names..): the segment index is then appended, as last resort.
+fn unique_filename(used: &mut HashSet<String>, filename: String, index: usize) -> String {
+    let filename = if used.contains(&filename) {
+        let renamed = match filename.split_once('.') {
+            Some((stem, extension)) => format!("{}_{:02}.{}", stem, index, extension),
+            None => format!("{}_{:02}", filename, index),
+        };
+        debug!(
+            "\"{}\" already generated: renamed \"{}\"",
+            filename, renamed
+        );
+        renamed
+    } else {
+        filename
+    };
+
+    used.insert(filename.clone());
+    filename
+}
+
+/// Splits input files at specified Time Instants, and/or at data gaps.
+/// Each segment is generated in the SPLIT subfolder.
 pub fn split(ctx: &Context, submatches: &ArgMatches) -> Result<(), Error> {
     let ctx_data = &ctx.data;
 
     let gzip = submatches.get_flag("gzip");
     let forced_short_v2 = submatches.get_flag("short");
 
-    let split_instant = submatches
-        .get_one::<Epoch>("split")
-        .expect("split epoch is required");
+    let instants = submatches
+        .get_many::<Epoch>("split")
+        .map(|instants| instants.copied().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let gap = submatches.get_one::<Duration>("gap").copied();
+
+    if instants.is_empty() && gap.is_none() {
+        return Err(Error::Configuration(
+            "split requires at least one epoch, or a data gap (--gap)".to_string(),
+        ));
+    }
+
+    if gap == Some(Duration::ZERO) {
+        return Err(Error::Configuration("invalid (null) data gap".to_string()));
+    }
+
+    ctx.workspace.create_subdir("SPLIT")?;
+
+    let mut used = HashSet::<String>::new();
 
     for product in [
         ProductType::Observation,
@@ -22,18 +123,119 @@ pub fn split(ctx: &Context, submatches: &ArgMatches) -> Result<(), Error> {
         ProductType::BroadcastNavigation,
         ProductType::HighPrecisionClock,
         ProductType::IONEX,
+        ProductType::DORIS,
     ] {
         if let Some(rinex) = ctx_data.rinex(product) {
-            let (rinex_a, rinex_b) = rinex.split(*split_instant);
+            let epochs = rinex.epoch_iter().collect::<BTreeSet<_>>();
+            let epochs = epochs.into_iter().collect::<Vec<_>>();
 
-            let input_name = rinex_a.standard_filename(forced_short_v2, None, None);
-            let input_path = Path::new(&input_name);
-            dump_rinex_auto_generated_name(&ctx, &input_path, &rinex_a, gzip, None);
+            let cuts = cut_points(&epochs, &instants, gap);
 
-            let input_name = rinex_b.standard_filename(forced_short_v2, None, None);
-            let input_path = Path::new(&input_name);
-            dump_rinex_auto_generated_name(&ctx, &input_path, &rinex_b, gzip, None);
+            if cuts.is_empty() {
+                warn!("{}: nothing to split", product);
+                continue;
+            }
+
+            for (index, (start, end)) in segments(&cuts).into_iter().enumerate() {
+                let mut segment = rinex.clone();
+
+                for filter in segment_filters(start, end).iter() {
+                    segment.filter_mut(filter);
+                }
+
+                let (Some(first), Some(last)) = (segment.first_epoch(), segment.last_epoch())
+                else {
+                    continue;
+                };
+
+                // standard name describes the segment
+                let mut prod = custom_prod_attributes(&segment, submatches);
+
+                if let Some(ref mut details) = prod.v3_details {
+                    details.batch = index as u8;
+
+                    if submatches.get_one::<PPU>("ppu").is_none() {
+                        if let Some(ppu) = covering_ppu(last - first) {
+                            details.ppu = ppu;
+                        }
+                    }
+                }
+
+                let suffix = if gzip { Some(".gz") } else { None };
+
+                let mut filename = segment.standard_filename(forced_short_v2, suffix, Some(prod));
+
+                // V2 short names describe sub daily segments with their session letter
+                if last - first < Duration::from_days(1.0) {
+                    filename = hourly_session_name(&filename, first);
+                }
+
+                let filename = unique_filename(&mut used, filename, index);
+
+                let output = ctx
+                    .workspace
+                    .root
+                    .join("SPLIT")
+                    .join(&filename)
+                    .to_string_lossy()
+                    .to_string();
+
+                write_rinex(ctx, &segment, &output, gzip)?;
+                info!("\"{}\" has been generated", output);
+            }
+        }
+    }
+
+    if let Some(sp3) = ctx_data.sp3() {
+        let epochs = sp3
+            .satellites_position_km_iter()
+            .map(|(t, _, _, _, _)| t)
+            .collect::<BTreeSet<_>>();
+
+        let epochs = epochs.into_iter().collect::<Vec<_>>();
+
+        let cuts = cut_points(&epochs, &instants, gap);
+
+        if cuts.is_empty() {
+            warn!("SP3: nothing to split");
+        } else {
+            for (index, (start, end)) in segments(&cuts).into_iter().enumerate() {
+                let mut segment = sp3.clone();
+
+                for filter in segment_filters(start, end).iter() {
+                    segment.filter_mut(filter);
+                }
+
+                if segment.first_epoch().is_none() {
+                    continue;
+                }
+
+                let mut filename = sp3_standard_filename(
+                    ctx,
+                    &segment,
+                    forced_short_v2,
+                    submatches.get_one::<String>("agency"),
+                )?;
+
+                if gzip {
+                    filename.push_str(".gz");
+                }
+
+                let filename = unique_filename(&mut used, filename, index);
+
+                let output = ctx
+                    .workspace
+                    .root
+                    .join("SPLIT")
+                    .join(&filename)
+                    .to_string_lossy()
+                    .to_string();
+
+                write_sp3(ctx, &segment, &output, gzip)?;
+                info!("\"{}\" has been generated", output);
+            }
         }
     }
+
     Ok(())
 }
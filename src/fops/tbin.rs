@@ -1,11 +1,59 @@
 use crate::cli::Context;
 use crate::fops::custom_prod_attributes;
 use crate::fops::output_filename;
+use crate::fops::write_rinex;
+use crate::fops::{epoch_filter, hourly_session_name, period_code, ppu_duration};
+use crate::fops::{sp3_output_filename, write_sp3};
 use crate::Error;
 use clap::ArgMatches;
-use gnss_qc::prelude::{Filter, Preprocessing, ProductType};
-use rinex::prelude::Duration;
-use rinex::prod::DetailedProductionAttributes;
+use gnss_qc::prelude::{MaskOperand, Preprocessing, ProductType, TimeScale};
+use rinex::prelude::{Duration, Epoch};
+use rinex::prod::{DetailedProductionAttributes, FFU, PPU};
+
+/// Production periodicity that matches this batch duration, if it is standardized
+fn batch_ppu(duration: Duration) -> Option<PPU> {
+    [PPU::QuarterHour, PPU::Hourly, PPU::Daily, PPU::Yearly]
+        .into_iter()
+        .find(|ppu| ppu_duration(*ppu) == Some(duration))
+}
+
+/// Batch time frames [start, end) covering [first, last].
+/// When aligned, batches start on multiples of the duration in that timescale
+/// (hourly batches on :00, daily batches on 00:00..).
+/// The final batch is partial when the data stops before its end,
+/// it is only emitted on request.
+fn batches(
+    first: Epoch,
+    last: Epoch,
+    duration: Duration,
+    sampling: Duration,
+    align: Option<TimeScale>,
+    partial: bool,
+) -> Vec<(Epoch, Epoch)> {
+    let mut start = match align {
+        Some(ts) => first
+            .to_time_scale(ts)
+            .floor(duration)
+            .to_time_scale(first.time_scale),
+        None => first,
+    };
+
+    let mut batches = Vec::new();
+
+    while start <= last {
+        let end = start + duration;
+
+        if end > last + sampling && !partial {
+            debug!("partial batch {} - {} dropped", start, last);
+            break;
+        }
+
+        batches.push((start, end));
+        start = end;
+    }
+
+    batches
+}
 
 /// Time binning (batch design)
 pub fn time_binning(
@@ -16,16 +64,37 @@ pub fn time_binning(
     let ctx_data = &ctx.data;
 
     let gzip = submatches.get_flag("gzip");
+    let short = submatches.get_flag("short");
+    let partial = submatches.get_flag("partial");
+    let align = submatches.get_one::<TimeScale>("align").copied();
 
     let duration = submatches
         .get_one::<Duration>("interval")
         .expect("duration is required");
 
     if *duration == Duration::ZERO {
-        panic!("invalid (null) duration");
+        return Err(Error::Configuration("invalid (null) duration".to_string()));
     }
 
-    ctx.workspace.create_subdir("BATCH");
+    let hourly = *duration == Duration::from_hours(1.0);
+
+    // production periodicity should describe the batch duration
+    let custom_ppu = submatches.get_one::<PPU>("ppu").copied();
+
+    if let Some(ppu) = custom_ppu {
+        if ppu_duration(ppu) != Some(*duration) {
+            warn!(
+                "--ppu {:?} does not match the batch duration ({})",
+                ppu, duration
+            );
+        }
+    }
+
+    ctx.workspace.create_subdir("BATCH")?;
+
+    if ctx_data.rinex(ProductType::ANTEX).is_some() {
+        info!("ANTEX is not time binned: antenna calibrations do not depend on time");
+    }
 
     for product in [
         ProductType::IONEX,
@@ -38,34 +107,65 @@ pub fn time_binning(
         // input data determination
         if let Some(rinex) = ctx_data.rinex(product) {
             // time frame determination
-            let (mut first, end) = (
-                rinex
-                    .first_epoch()
-                    .expect("failed to determine first epoch"),
-                rinex.last_epoch().expect("failed to determine last epoch"),
-            );
+            let (first, end) = match (rinex.first_epoch(), rinex.last_epoch()) {
+                (Some(first), Some(end)) => (first, end),
+                _ => {
+                    return Err(Error::InvalidInput(format!(
+                        "{} does not contain any epoch",
+                        product
+                    )));
+                },
+            };
 
-            let mut last = first + *duration;
+            let sampling = rinex.dominant_sampling_interval();
 
-            // production attributes: initialize Batch counter
-            let mut prod = custom_prod_attributes(rinex, submatches);
+            // sampling period should match
+            if let (Some(ffu), Some(sampling)) = (submatches.get_one::<FFU>("ffu"), sampling) {
+                if ffu.to_string() != period_code(sampling) {
+                    warn!(
+                        "{}: --ffu {} does not match the sampling period ({})",
+                        product, ffu, sampling
+                    );
+                }
+            }
 
-            if let Some(ref mut details) = prod.v3_details {
-                details.batch = 0_u8;
-            } else {
-                let mut details = DetailedProductionAttributes::default();
-                details.batch = 0_u8;
-                prod.v3_details = Some(details);
-            };
+            let sampling = sampling.unwrap_or(Duration::ZERO);
 
             // run time binning algorithm
-            while last <= end {
-                let lower = Filter::lower_than(&last.to_string()).unwrap();
-                let greater = Filter::greater_equals(&first.to_string()).unwrap();
+            for (batch, (first, last)) in batches(first, end, *duration, sampling, align, partial)
+                .into_iter()
+                .enumerate()
+            {
+                let lower = epoch_filter(MaskOperand::LowerThan, last);
+                let greater = epoch_filter(MaskOperand::GreaterEquals, first);
                 let batched = rinex.filter(&lower).filter(&greater);
 
+                if batched.first_epoch().is_none() {
+                    debug!("{}: empty batch {} - {}", product, first, last);
+                    continue;
+                }
+
+                // production attributes describe this batch
+                let mut prod = custom_prod_attributes(&batched, submatches);
+
+                let details = prod
+                    .v3_details
+                    .get_or_insert_with(DetailedProductionAttributes::default);
+
+                details.batch = batch as u8;
+
+                if custom_ppu.is_none() {
+                    if let Some(ppu) = batch_ppu(*duration) {
+                        details.ppu = ppu;
+                    }
+                }
+
                 // generate standardized name
-                let filename = output_filename(&batched, matches, submatches, prod.clone());
+                let mut filename = output_filename(&batched, matches, submatches, prod);
+
+                if short && hourly && matches.get_one::<String>("output-name").is_none() {
+                    filename = hourly_session_name(&filename, first);
+                }
 
                 let output = ctx
                     .workspace
@@ -75,24 +175,47 @@ pub fn time_binning(
                     .to_string_lossy()
                     .to_string();
 
-                if gzip {
-                    batched
-                        .to_gzip_file(&output)
-                        .unwrap_or_else(|e| panic!("Failed to format RINEX {}: {}", output, e));
-                } else {
-                    batched
-                        .to_file(&output)
-                        .unwrap_or_else(|e| panic!("Failed to format RINEX {}: {}", output, e));
-                }
+                write_rinex(ctx, &batched, &output, gzip)?;
+            }
+        }
+    }
 
-                first += *duration;
-                last += *duration;
+    if let Some(sp3) = ctx_data.sp3() {
+        let (first, end) = match (sp3.first_epoch(), sp3.last_epoch()) {
+            (Some(first), Some(end)) => (first, end),
+            _ => {
+                return Err(Error::InvalidInput(
+                    "SP3 does not contain any epoch".to_string(),
+                ));
+            },
+        };
 
-                if let Some(ref mut details) = prod.v3_details {
-                    details.batch += 1;
-                }
+        let sampling = sp3.header.epoch_interval;
+
+        for (first, last) in batches(first, end, *duration, sampling, align, partial) {
+            let lower = epoch_filter(MaskOperand::LowerThan, last);
+            let greater = epoch_filter(MaskOperand::GreaterEquals, first);
+            let batched = sp3.filter(&lower).filter(&greater);
+
+            if batched.first_epoch().is_none() {
+                debug!("SP3: empty batch {} - {}", first, last);
+                continue;
             }
+
+            // standard name describes the batch itself
+            let filename = sp3_output_filename(ctx, &batched, matches, submatches)?;
+
+            let output = ctx
+                .workspace
+                .root
+                .join("BATCH")
+                .join(&filename)
+                .to_string_lossy()
+                .to_string();
+
+            write_sp3(ctx, &batched, &output, gzip)?;
         }
     }
+
     Ok(())
 }
@@ -0,0 +1,42 @@
+//! Transparent decompression of input files
+use std::{
+    fs::{read, File},
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+
+use crate::loader::{lzw, Compression, Error};
+
+/// Returns a [Read]able over the decompressed content of this file.
+/// Unix compress (.Z) has no streaming decoder: it is decoded in memory.
+pub fn reader(path: &Path, compression: Compression) -> Result<Box<dyn Read>, Error> {
+    match compression {
+        Compression::None => Ok(Box::new(File::open(path)?)),
+        Compression::Gzip => Ok(Box::new(GzDecoder::new(File::open(path)?))),
+        Compression::Bzip2 => Ok(Box::new(BzDecoder::new(File::open(path)?))),
+        Compression::Lzw => {
+            let content = lzw::decompress(&read(path)?, None)?;
+            Ok(Box::new(Cursor::new(content)))
+        },
+    }
+}
+
+/// Returns up to size bytes of decompressed content
+pub fn head(path: &Path, compression: Compression, size: usize) -> Result<Vec<u8>, Error> {
+    match compression {
+        Compression::Lzw => lzw::decompress(&read(path)?, Some(size)),
+        compression => {
+            let mut head = Vec::with_capacity(size);
+            let mut reader = reader(path, compression)?.take(size as u64);
+
+            match reader.read_to_end(&mut head) {
+                Ok(_) => Ok(head),
+                Err(e) if compression == Compression::None => Err(Error::StdioError(e)),
+                Err(_) => Err(Error::CorruptedCompression),
+            }
+        },
+    }
+}
@@ -0,0 +1,140 @@
+//! Input files selection
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use glob::{glob, Pattern};
+use walkdir::WalkDir;
+
+use crate::loader::{Preview, Selection};
+
+/// Input files description, as defined by User
+#[derive(Debug, Default, Clone)]
+pub struct Inputs {
+    /// Individual files (or glob patterns)
+    pub files: Vec<String>,
+    /// Files listing one path (or glob pattern) per line
+    pub lists: Vec<PathBuf>,
+    /// Directories to walk recursively
+    pub directories: Vec<String>,
+    /// Maximal recursive depth
+    pub max_depth: usize,
+    /// Retain only file names matching one of these patterns (directories only)
+    pub include: Vec<Pattern>,
+    /// Reject file names matching one of these patterns (directories only)
+    pub exclude: Vec<Pattern>,
+    /// Time window and station pre-selection
+    pub selection: Selection,
+}
+
+/// True if this description should be expanded as a glob pattern
+fn is_glob(desc: &str) -> bool {
+    desc.contains(['*', '?', '['])
+}
+
+/// Expands a single file description, that is possibly a glob pattern
+fn expand(desc: &str, paths: &mut Vec<PathBuf>) {
+    if !is_glob(desc) {
+        paths.push(PathBuf::from(desc));
+        return;
+    }
+
+    match glob(desc) {
+        Ok(entries) => {
+            let mut matches = entries.filter_map(|e| e.ok()).collect::<Vec<_>>();
+
+            if matches.is_empty() {
+                warn!("\"{}\" did not match any file", desc);
+            }
+
+            matches.sort();
+            paths.append(&mut matches);
+        },
+        Err(e) => {
+            error!("invalid glob pattern \"{}\": {}", desc, e);
+        },
+    }
+}
+
+impl Inputs {
+    /// True if this file name passes the include/exclude patterns
+    fn name_selected(&self, path: &Path) -> bool {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return false,
+        };
+
+        if !self.include.is_empty() && !self.include.iter().any(|p| p.matches(&name)) {
+            return false;
+        }
+
+        !self.exclude.iter().any(|p| p.matches(&name))
+    }
+
+    /// Returns the list of files to load, in a deterministic order:
+    /// directory content (sorted by name), then file lists, then individual files.
+    /// Files that do not pass the [Selection] are not returned.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::<PathBuf>::new();
+
+        // recursive dir loader
+        for dir in self.directories.iter() {
+            let walkdir = WalkDir::new(dir)
+                .max_depth(self.max_depth)
+                .sort_by_file_name();
+
+            for entry in walkdir.into_iter().filter_map(|e| e.ok()) {
+                let path = entry.path();
+                if !path.is_dir() {
+                    if self.name_selected(path) {
+                        paths.push(path.to_path_buf());
+                    } else {
+                        trace!("\"{}\" not selected", path.display());
+                    }
+                }
+            }
+        }
+
+        // file lists
+        for list in self.lists.iter() {
+            match read_to_string(list) {
+                Ok(content) => {
+                    for line in content.lines() {
+                        let line = line.trim();
+                        if !line.is_empty() && !line.starts_with('#') {
+                            expand(line, &mut paths);
+                        }
+                    }
+                },
+                Err(e) => {
+                    error!("failed to read file list \"{}\": {}", list.display(), e);
+                },
+            }
+        }
+
+        // individual files
+        for fp in self.files.iter() {
+            expand(fp, &mut paths);
+        }
+
+        if !self.selection.is_empty() {
+            let total = paths.len();
+
+            paths.retain(|path| match Preview::from_path(path) {
+                Some(preview) => {
+                    let retained = self.selection.retains(&preview);
+                    if !retained {
+                        debug!("\"{}\" not selected: {:?}", path.display(), preview);
+                    }
+                    retained
+                },
+                None => true,
+            });
+
+            info!("{}/{} files pre-selected", paths.len(), total);
+        }
+
+        paths
+    }
+}
@@ -0,0 +1,190 @@
+//! Unix compress (.Z) decoder.
+//! The historical `compress` tool uses a variable length LZW scheme
+//! (9 to 16 bit codes, LSB first), with one particularity: codes are
+//! written in groups of 8, and each code size change or table reset
+//! pads the stream to the end of the current group.
+use crate::loader::Error;
+
+/// Codes start with this size
+const INIT_BITS: u32 = 9;
+
+/// Table reset code (block mode only)
+const CLEAR: u32 = 256;
+
+/// Flags byte: maximal code size
+const BITS_MASK: u8 = 0x1f;
+
+/// Flags byte: block (reset) mode
+const BLOCK_MODE: u8 = 0x80;
+
+/// LSB first bit reader
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Current position, in bits
+    pos: usize,
+    /// Position where the current code size (or table) started, in bits
+    group_start: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            group_start: 0,
+        }
+    }
+
+    /// Reads next code of n_bits, returns None on end of stream
+    fn read(&mut self, n_bits: u32) -> Option<u32> {
+        let n_bits = n_bits as usize;
+
+        if self.pos + n_bits > self.data.len() * 8 {
+            return None;
+        }
+
+        let byte = self.pos / 8;
+        let offset = self.pos % 8;
+
+        // a code spans 3 bytes at most (16 bits + 7 offset)
+        let mut word = 0_u32;
+        for i in 0..3 {
+            if let Some(b) = self.data.get(byte + i) {
+                word |= (*b as u32) << (8 * i);
+            }
+        }
+
+        self.pos += n_bits;
+        Some((word >> offset) & ((1 << n_bits) - 1))
+    }
+
+    /// Skips to the end of the current group of 8 codes of n_bits,
+    /// as the compressor did when it changed code size or reset its table.
+    fn align(&mut self, n_bits: u32) {
+        let group_bits = n_bits as usize * 8;
+        let consumed = self.pos - self.group_start;
+        let remainder = consumed % group_bits;
+
+        if remainder != 0 {
+            self.pos += group_bits - remainder;
+        }
+
+        self.group_start = self.pos;
+    }
+}
+
+/// Decompresses Unix compress (.Z) content.
+/// When limit is defined, we stop as soon as that many bytes were produced,
+/// which is enough to identify a file without decoding it entirely.
+pub fn decompress(data: &[u8], limit: Option<usize>) -> Result<Vec<u8>, Error> {
+    if data.len() < 3 || data[0] != 0x1f || data[1] != 0x9d {
+        return Err(Error::CorruptedCompression);
+    }
+
+    let max_bits = (data[2] & BITS_MASK) as u32;
+    let block_mode = data[2] & BLOCK_MODE > 0;
+
+    if !(INIT_BITS..=16).contains(&max_bits) {
+        return Err(Error::CorruptedCompression);
+    }
+
+    let max_max_code = 1_u32 << max_bits;
+    let first_free = if block_mode { CLEAR + 1 } else { CLEAR };
+
+    let mut prefix = vec![0_u32; max_max_code as usize];
+    let mut suffix = vec![0_u8; max_max_code as usize];
+
+    for (code, symbol) in suffix.iter_mut().enumerate().take(256) {
+        *symbol = code as u8;
+    }
+
+    let mut output = Vec::<u8>::with_capacity(data.len() * 4);
+    let mut stack = Vec::<u8>::with_capacity(1024);
+
+    let mut reader = BitReader::new(&data[3..]);
+
+    let mut n_bits = INIT_BITS;
+    let mut max_code = (1 << n_bits) - 1;
+    let mut free_entry = first_free;
+
+    let mut previous = Option::<u32>::None;
+    let mut first_char = 0_u8;
+
+    loop {
+        if let Some(limit) = limit {
+            if output.len() >= limit {
+                output.truncate(limit);
+                break;
+            }
+        }
+
+        if free_entry > max_code {
+            reader.align(n_bits);
+            n_bits += 1;
+            max_code = if n_bits == max_bits {
+                max_max_code
+            } else {
+                (1 << n_bits) - 1
+            };
+        }
+
+        let code = match reader.read(n_bits) {
+            Some(code) => code,
+            None => break,
+        };
+
+        let prev = match previous {
+            Some(prev) => prev,
+            None => {
+                // first code of a table: literal
+                if code > 255 {
+                    return Err(Error::CorruptedCompression);
+                }
+                first_char = code as u8;
+                output.push(first_char);
+                previous = Some(code);
+                continue;
+            },
+        };
+
+        if block_mode && code == CLEAR {
+            reader.align(n_bits);
+            n_bits = INIT_BITS;
+            max_code = (1 << n_bits) - 1;
+            free_entry = first_free;
+            previous = None;
+            continue;
+        }
+
+        let mut current = code;
+
+        if code >= free_entry {
+            // KwKwK special case
+            if code > free_entry {
+                return Err(Error::CorruptedCompression);
+            }
+            stack.push(first_char);
+            current = prev;
+        }
+
+        while current >= 256 {
+            stack.push(suffix[current as usize]);
+            current = prefix[current as usize];
+        }
+
+        first_char = suffix[current as usize];
+        stack.push(first_char);
+
+        output.extend(stack.drain(..).rev());
+
+        if free_entry < max_max_code {
+            prefix[free_entry as usize] = prev;
+            suffix[free_entry as usize] = first_char;
+            free_entry += 1;
+        }
+
+        previous = Some(code);
+    }
+
+    Ok(output)
+}
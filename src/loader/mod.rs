@@ -0,0 +1,119 @@
+//! Input products loader
+use std::{io::BufReader, path::Path};
+
+use gnss_qc::prelude::{ProductType, QcContext, Rinex, SP3};
+use rinex::prelude::RinexType;
+use thiserror::Error;
+
+mod decompress;
+mod inputs;
+mod lzw;
+mod parallel;
+mod selection;
+mod sniffer;
+mod stations;
+
+pub use inputs::Inputs;
+pub use parallel::load_files;
+pub use selection::{Preview, Selection};
+pub use sniffer::{sniff, Compression, Format, Sniffed};
+pub use stations::{Station, Stations};
+
+/// Errors that may happen while loading one input file.
+/// None of these abort the session: the file is reported and skipped.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("i/o error: {0}")]
+    StdioError(#[from] std::io::Error),
+    #[error("unknown file format")]
+    UnknownFormat,
+    #[error("corrupted compressed file")]
+    CorruptedCompression,
+    #[error("{0} loading error: {1}")]
+    Loading(Format, String),
+}
+
+/// Parsed input product, ready to be stacked into a [QcContext]
+#[derive(Clone)]
+pub enum Product {
+    Rinex(Rinex),
+    SP3(SP3),
+}
+
+impl Product {
+    /// Returns the [ProductType] of this [Product]
+    pub fn product_type(&self) -> ProductType {
+        match self {
+            Self::SP3(_) => ProductType::HighPrecisionOrbit,
+            Self::Rinex(rinex) => rinex_product_type(rinex),
+        }
+    }
+
+    /// Stacks this [Product] into [QcContext]
+    fn load(self, ctx: &mut QcContext, path: &Path) -> Result<(), String> {
+        match self {
+            Self::Rinex(rinex) => ctx.load_rinex(path, rinex).map_err(|e| e.to_string()),
+            Self::SP3(sp3) => ctx.load_sp3(path, sp3).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Returns the [ProductType] of this [Rinex]
+pub fn rinex_product_type(rinex: &Rinex) -> ProductType {
+    match rinex.header.rinex_type {
+        RinexType::ObservationData => ProductType::Observation,
+        RinexType::NavigationData => ProductType::BroadcastNavigation,
+        RinexType::MeteoData => ProductType::MeteoObservation,
+        RinexType::ClockData => ProductType::HighPrecisionClock,
+        RinexType::IonosphereMaps => ProductType::IONEX,
+        RinexType::AntennaData => ProductType::ANTEX,
+        RinexType::DORIS => ProductType::DORIS,
+    }
+}
+
+/// Identifies and parses a single file.
+/// This does not involve any [QcContext] and may run in a worker thread.
+pub fn parse_file(path: &Path) -> Result<(Sniffed, Product), Error> {
+    let sniffed = sniff(path)?;
+
+    let product = if sniffed.format.is_sp3() {
+        let parsed = match sniffed.compression {
+            Compression::None => SP3::from_file(path),
+            Compression::Gzip => SP3::from_gzip_file(path),
+            compression => {
+                // decompressed on our side
+                let mut reader = BufReader::new(decompress::reader(path, compression)?);
+                SP3::from_reader(&mut reader)
+            },
+        };
+
+        let sp3 = parsed.map_err(|e| Error::Loading(sniffed.format, e.to_string()))?;
+        Product::SP3(sp3)
+    } else {
+        let parsed = match sniffed.compression {
+            Compression::None => Rinex::from_file(path),
+            Compression::Gzip => Rinex::from_gzip_file(path),
+            compression => {
+                // decompressed on our side
+                let mut reader = BufReader::new(decompress::reader(path, compression)?);
+                Rinex::parse(&mut reader)
+            },
+        };
+
+        let rinex = parsed.map_err(|e| Error::Loading(sniffed.format, e.to_string()))?;
+        Product::Rinex(rinex)
+    };
+
+    Ok((sniffed, product))
+}
+
+/// Parses a single RINEX file, whatever its compression.
+pub fn parse_rinex(path: &Path) -> Result<Rinex, Error> {
+    match parse_file(path)? {
+        (_, Product::Rinex(rinex)) => Ok(rinex),
+        (sniffed, Product::SP3(_)) => Err(Error::Loading(
+            sniffed.format,
+            "expecting RINEX, not SP3".to_string(),
+        )),
+    }
+}
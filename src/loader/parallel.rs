@@ -0,0 +1,149 @@
+//! Parallel file loading
+use std::{
+    collections::BTreeMap,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use crate::loader::{parse_file, Error, Product, Sniffed, Stations};
+
+type Parsed = Result<(Sniffed, Product), Error>;
+
+/// Progress indicator, printed on stderr when it is a terminal
+struct Progress {
+    total: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    fn update(&self, done: usize) {
+        if self.enabled {
+            let mut stderr = std::io::stderr();
+            let _ = write!(stderr, "\rloading files: {}/{}", done, self.total);
+            let _ = stderr.flush();
+        }
+    }
+
+    fn finish(&self) {
+        if self.enabled {
+            eprintln!();
+        }
+    }
+}
+
+/// Stacks one parsed file into [Stations], reports and skips on failure.
+/// Returns true when the file was loaded.
+fn stack(stations: &mut Stations, path: &Path, parsed: Parsed) -> bool {
+    let loaded = parsed.and_then(|(sniffed, product)| {
+        stations
+            .stack(path, product)
+            .map_err(|e| Error::Loading(sniffed.format, e))?;
+        Ok(sniffed)
+    });
+
+    match loaded {
+        Ok(sniffed) => {
+            info!(
+                "{} file loaded ({} compression): \"{}\"",
+                sniffed.format,
+                sniffed.compression,
+                path.display()
+            );
+            true
+        },
+        Err(e) => {
+            warn!("\"{}\" skipped: {}", path.display(), e);
+            false
+        },
+    }
+}
+
+/// Parses all files using up to `jobs` worker threads, and stacks them
+/// into [Stations]. Files are always stacked in the order they were
+/// provided, whatever the order of completion, so the resulting contexts
+/// do not depend on the number of workers.
+/// Returns the number of files that were loaded.
+pub fn load_files(
+    stations: &mut Stations,
+    paths: &[PathBuf],
+    jobs: usize,
+    progress: bool,
+) -> usize {
+    let total = paths.len();
+    let jobs = jobs.clamp(1, total.max(1));
+
+    let progress = Progress {
+        total,
+        enabled: progress && total > 0 && std::io::stderr().is_terminal(),
+    };
+
+    let mut loaded = 0;
+
+    // input selection
+    stations.collect_logs();
+
+    if jobs == 1 {
+        for (index, path) in paths.iter().enumerate() {
+            if stack(stations, path, parse_file(path)) {
+                loaded += 1;
+            }
+            stations.collect_logs();
+            progress.update(index + 1);
+        }
+        progress.finish();
+        return loaded;
+    }
+
+    debug!("loading {} files with {} workers", total, jobs);
+
+    let next_job = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel::<(usize, Parsed)>();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            let next_job = &next_job;
+
+            scope.spawn(move || loop {
+                let index = next_job.fetch_add(1, Ordering::Relaxed);
+
+                if index >= total {
+                    break;
+                }
+
+                if tx.send((index, parse_file(&paths[index]))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // workers own the remaining senders
+        drop(tx);
+
+        // completed but not stackable yet (waiting for previous files)
+        let mut pending = BTreeMap::<usize, Parsed>::new();
+        let mut next_stack = 0;
+
+        for (done, (index, parsed)) in rx.iter().enumerate() {
+            pending.insert(index, parsed);
+
+            while let Some(parsed) = pending.remove(&next_stack) {
+                if stack(stations, &paths[next_stack], parsed) {
+                    loaded += 1;
+                }
+                stations.collect_logs();
+                next_stack += 1;
+            }
+
+            progress.update(done + 1);
+        }
+    });
+
+    progress.finish();
+    loaded
+}
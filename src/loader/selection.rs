@@ -0,0 +1,330 @@
+//! Files pre-selection, prior parsing.
+//! We use the standard (V3 or V2) file name when possible, otherwise we only parse the file header.
+use std::{path::Path, str::FromStr};
+
+use gnss_qc::prelude::{Duration, Epoch, TimeScale};
+
+use crate::loader::{decompress, sniff};
+
+/// Header section we inspect, when the file name is not standard
+const HEADER_SIZE: usize = 65536;
+
+/// Nominal period of Observation RINEX whose name is not standard
+/// and whose header does not describe the last epoch (daily production)
+const NOMINAL_PERIOD_DAYS: f64 = 1.0;
+
+/// Time window and station selection, defined by User
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    /// Retain files of these stations only
+    pub stations: Vec<String>,
+    /// Retain files that end after this instant
+    pub start: Option<Epoch>,
+    /// Retain files that start before this instant
+    pub end: Option<Epoch>,
+}
+
+/// What we know about a file, without parsing it entirely
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Preview {
+    /// Station name, only for station dependent products (OBS, Meteo)
+    pub station: Option<String>,
+    /// First epoch
+    pub start: Option<Epoch>,
+    /// Last epoch
+    pub end: Option<Epoch>,
+}
+
+/// Parses a standard period (PPU), for example "01D"
+fn parse_period(ppu: &str) -> Option<Option<Duration>> {
+    if ppu.len() != 3 {
+        return None;
+    }
+
+    let value = u32::from_str(&ppu[..2]).ok()? as f64;
+
+    match &ppu[2..] {
+        "M" => Some(Some(Duration::from_seconds(value * 60.0))),
+        "H" => Some(Some(Duration::from_hours(value))),
+        "D" => Some(Some(Duration::from_days(value))),
+        "Y" => Some(Some(Duration::from_days(value * 365.25))),
+        // unspecified
+        "U" => Some(None),
+        _ => None,
+    }
+}
+
+/// Parses a standard start time, YYYYDDDHHMM, expressed in GPST
+fn parse_start_time(desc: &str) -> Option<Epoch> {
+    if desc.len() != 11 || !desc.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let year = i32::from_str(&desc[..4]).ok()?;
+    let doy = u32::from_str(&desc[4..7]).ok()?;
+    let hours = u32::from_str(&desc[7..9]).ok()?;
+    let minutes = u32::from_str(&desc[9..11]).ok()?;
+
+    if doy == 0 || doy > 366 || hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    let t = Epoch::from_gregorian_at_midnight(year, 1, 1, TimeScale::GPST)
+        + Duration::from_days((doy - 1) as f64)
+        + Duration::from_hours(hours as f64)
+        + Duration::from_seconds(minutes as f64 * 60.0);
+
+    Some(t)
+}
+
+impl Preview {
+    /// Obtain [Preview] from a V2 short file name, like "esbc1770.20o" or "esbc177a.20d.Z",
+    /// expressed in GPST. The session letter is either '0' (daily file)
+    /// or 'a' to 'x' (hourly file, 'a' starting at 00:00).
+    fn from_short_name(name: &str) -> Option<Self> {
+        let mut items = name.split('.');
+        let stem = items.next()?;
+        let extension = items.next()?;
+
+        if stem.len() != 8 || extension.len() != 3 || !stem.is_ascii() || !extension.is_ascii() {
+            return None;
+        }
+
+        let is_number = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+
+        if !is_number(&stem[4..7]) || !is_number(&extension[..2]) {
+            return None;
+        }
+
+        let doy = u32::from_str(&stem[4..7]).ok()?;
+        let yy = i32::from_str(&extension[..2]).ok()?;
+        let year = if yy < 80 { 2000 + yy } else { 1900 + yy };
+
+        if doy == 0 || doy > 366 {
+            return None;
+        }
+
+        let (offset, period) = match stem.as_bytes()[7].to_ascii_lowercase() {
+            b'0' => (Duration::ZERO, Duration::from_days(1.0)),
+            session @ b'a'..=b'x' => (
+                Duration::from_hours((session - b'a') as f64),
+                Duration::from_hours(1.0),
+            ),
+            _ => return None,
+        };
+
+        let start = Epoch::from_gregorian_at_midnight(year, 1, 1, TimeScale::GPST)
+            + Duration::from_days((doy - 1) as f64)
+            + offset;
+
+        // only Observations (CRINEX included) and Meteo are tied to one station
+        let station_dependent = matches!(
+            extension.as_bytes()[2].to_ascii_lowercase(),
+            b'o' | b'd' | b'm'
+        );
+
+        Some(Self {
+            station: if station_dependent {
+                Some(stem[..4].to_uppercase())
+            } else {
+                None
+            },
+            start: Some(start),
+            end: Some(start + period),
+        })
+    }
+
+    /// Obtain [Preview] from a standard V3 or V2 file name.
+    /// Station files look like "ESBC00DNK_R_20201770000_01D_30S_MO.crx.gz" or "esbc1770.20d.Z",
+    /// products look like "GRG0MGXFIN_20201770000_01D_15M_ORB.SP3.gz".
+    pub fn from_standard_name(name: &str) -> Option<Self> {
+        if let Some(preview) = Self::from_short_name(name) {
+            return Some(preview);
+        }
+
+        let stem = name.split('.').next()?;
+        let items = stem.split('_').collect::<Vec<_>>();
+
+        let (station, start, period, content) =
+            if items.len() >= 5 && items[0].len() == 9 && items[1].len() == 1 {
+                (Some(items[0]), items[2], items[3], items[items.len() - 1])
+            } else if items.len() >= 4 && items[0].len() == 10 {
+                (None, items[1], items[2], items[items.len() - 1])
+            } else {
+                return None;
+            };
+
+        let start = parse_start_time(start)?;
+        let period = parse_period(period)?;
+
+        // only Observations and Meteo are tied to one station.
+        // Navigation for example, may apply to other stations.
+        let station_dependent = content.len() == 2 && matches!(&content[1..], "O" | "M");
+
+        Some(Self {
+            station: if station_dependent {
+                station.map(|s| s.to_uppercase())
+            } else {
+                None
+            },
+            start: Some(start),
+            end: period.map(|period| start + period),
+        })
+    }
+
+    /// Obtain [Preview] from the header section of a RINEX or SP3 file
+    pub fn from_header(content: &str) -> Self {
+        let mut preview = Self::default();
+        let mut lines = content.lines();
+
+        let first = match lines.next() {
+            Some(first) => first,
+            None => return preview,
+        };
+
+        if first.starts_with('#') {
+            // SP3: first line describes first epoch and number of epochs,
+            // second line describes the sampling period
+            let start = Self::parse_calendar(first.get(3..31).unwrap_or(""), TimeScale::GPST);
+            let num_epochs = first.get(32..39).and_then(|n| u32::from_str(n.trim()).ok());
+
+            let interval = lines
+                .next()
+                .and_then(|second| second.get(24..38))
+                .and_then(|dt| f64::from_str(dt.trim()).ok());
+
+            preview.start = start;
+
+            if let (Some(start), Some(n), Some(dt)) = (start, num_epochs, interval) {
+                preview.end = Some(start + Duration::from_seconds(dt * n.saturating_sub(1) as f64));
+            }
+
+            return preview;
+        }
+
+        // RINEX: station dependent files are identified by the type letter.
+        // CRINEX is always Observation RINEX.
+        let station_dependent =
+            first.contains("CRINEX VERS") || matches!(first.get(20..21), Some("O") | Some("M"));
+
+        for line in content.lines() {
+            let label = line.get(60..).unwrap_or("").trim();
+            let data = line.get(..60).unwrap_or(line);
+
+            match label {
+                "MARKER NAME" => {
+                    if station_dependent {
+                        let name = data.trim();
+                        if !name.is_empty() {
+                            preview.station = Some(name.to_uppercase());
+                        }
+                    }
+                },
+                "TIME OF FIRST OBS" => {
+                    preview.start = Self::parse_time_of_obs(data);
+                },
+                "TIME OF LAST OBS" => {
+                    preview.end = Self::parse_time_of_obs(data);
+                },
+                "END OF HEADER" => break,
+                _ => {},
+            }
+        }
+
+        // "TIME OF LAST OBS" is optional
+        if preview.end.is_none() {
+            preview.end = preview
+                .start
+                .map(|start| start + Duration::from_days(NOMINAL_PERIOD_DAYS));
+        }
+
+        preview
+    }
+
+    /// Parses "YYYY MM DD HH MM SS.SSSS", in [TimeScale]
+    fn parse_calendar(desc: &str, ts: TimeScale) -> Option<Epoch> {
+        let items = desc.split_ascii_whitespace().collect::<Vec<_>>();
+
+        if items.len() < 6 {
+            return None;
+        }
+
+        let year = i32::from_str(items[0]).ok()?;
+        let month = u8::from_str(items[1]).ok()?;
+        let day = u8::from_str(items[2]).ok()?;
+        let hours = u8::from_str(items[3]).ok()?;
+        let minutes = u8::from_str(items[4]).ok()?;
+        let seconds = f64::from_str(items[5]).ok()?;
+
+        let t = Epoch::maybe_from_gregorian(year, month, day, hours, minutes, 0, 0, ts).ok()?;
+        Some(t + Duration::from_seconds(seconds))
+    }
+
+    /// Parses "TIME OF FIRST/LAST OBS" content
+    fn parse_time_of_obs(data: &str) -> Option<Epoch> {
+        let ts = match data.get(48..51).map(|ts| ts.trim()) {
+            Some("GLO") => TimeScale::UTC,
+            Some("GAL") => TimeScale::GST,
+            Some("BDT") => TimeScale::BDT,
+            Some("QZS") => TimeScale::QZSST,
+            _ => TimeScale::GPST,
+        };
+
+        Self::parse_calendar(data.get(..43).unwrap_or(data), ts)
+    }
+
+    /// Obtain [Preview] for this file
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_string();
+
+        if let Some(preview) = Self::from_standard_name(&name) {
+            return Some(preview);
+        }
+
+        // not standard: header parsing
+        let sniffed = sniff(path).ok()?;
+        let head = decompress::head(path, sniffed.compression, HEADER_SIZE).ok()?;
+        let head = String::from_utf8_lossy(&head);
+        Some(Self::from_header(&head))
+    }
+}
+
+impl Selection {
+    /// True if this [Selection] does not restrict anything
+    pub fn is_empty(&self) -> bool {
+        self.stations.is_empty() && self.start.is_none() && self.end.is_none()
+    }
+
+    /// True if this station name is selected
+    fn station_selected(&self, station: &str) -> bool {
+        self.stations.is_empty()
+            || self
+                .stations
+                .iter()
+                .any(|selected| station.starts_with(&selected.to_uppercase()))
+    }
+
+    /// True if this [Preview] passes the [Selection].
+    /// Whatever we could not determine is retained.
+    pub fn retains(&self, preview: &Preview) -> bool {
+        if let Some(station) = &preview.station {
+            if !self.station_selected(station) {
+                return false;
+            }
+        }
+
+        if let (Some(start), Some(file_end)) = (self.start, preview.end) {
+            if file_end < start {
+                return false;
+            }
+        }
+
+        if let (Some(end), Some(file_start)) = (self.end, preview.start) {
+            if file_start > end {
+                return false;
+            }
+        }
+
+        true
+    }
+}
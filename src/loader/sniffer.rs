@@ -0,0 +1,159 @@
+//! Content based file format detection
+use std::{
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use crate::loader::{decompress, Error};
+
+/// Number of (decompressed) bytes we inspect to determine the format
+const SNIFF_SIZE: usize = 4096;
+
+/// Gzip magic bytes
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Unix compress (LZW, .Z) magic bytes
+const LZW_MAGIC: [u8; 2] = [0x1f, 0x9d];
+
+/// Bzip2 magic bytes
+const BZIP2_MAGIC: [u8; 3] = [b'B', b'Z', b'h'];
+
+/// File compression, determined from magic bytes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain (readable) file
+    None,
+    /// Gzip compressed file
+    Gzip,
+    /// Unix compress (LZW) file, usually named .Z
+    Lzw,
+    /// Bzip2 compressed file
+    Bzip2,
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Gzip => write!(f, "gzip"),
+            Self::Lzw => write!(f, "lzw (.Z)"),
+            Self::Bzip2 => write!(f, "bzip2"),
+        }
+    }
+}
+
+impl Compression {
+    /// Determines [Compression] from leading bytes
+    fn from_magic(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&GZIP_MAGIC) {
+            Self::Gzip
+        } else if bytes.starts_with(&LZW_MAGIC) {
+            Self::Lzw
+        } else if bytes.starts_with(&BZIP2_MAGIC) {
+            Self::Bzip2
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// File format, determined from the (decompressed) content
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Readable RINEX (Observation, Navigation, Meteo, Clock, DORIS)
+    RINEX,
+    /// Hatanaka compressed Observation RINEX
+    CRINEX,
+    /// IONEX (Ionosphere maps)
+    IONEX,
+    /// ANTEX (antenna calibration)
+    ANTEX,
+    /// SP3 (high precision orbits)
+    SP3,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::RINEX => write!(f, "RINEX"),
+            Self::CRINEX => write!(f, "CRINEX"),
+            Self::IONEX => write!(f, "IONEX"),
+            Self::ANTEX => write!(f, "ANTEX"),
+            Self::SP3 => write!(f, "SP3"),
+        }
+    }
+}
+
+impl Format {
+    /// Determines [Format] from the first line of a file
+    fn from_first_line(line: &str) -> Option<Self> {
+        // SP3 (all revisions) start with #[a-d]
+        let bytes = line.as_bytes();
+        if bytes.len() > 2 && bytes[0] == b'#' && matches!(bytes[1], b'a'..=b'd') {
+            return Some(Self::SP3);
+        }
+
+        // RINEX like formats describe themselves in the first header label.
+        // CRINEX must be tested first, because its label contains "RINEX VERS".
+        if line.contains("CRINEX VERS") {
+            Some(Self::CRINEX)
+        } else if line.contains("RINEX VERSION / TYPE") {
+            Some(Self::RINEX)
+        } else if line.contains("IONEX VERSION / TYPE") {
+            Some(Self::IONEX)
+        } else if line.contains("ANTEX VERSION / SYST") {
+            Some(Self::ANTEX)
+        } else {
+            None
+        }
+    }
+
+    /// True if this [Format] should be parsed with the SP3 parser
+    pub fn is_sp3(&self) -> bool {
+        matches!(self, Self::SP3)
+    }
+}
+
+/// Content sniffing result
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Sniffed {
+    /// [Compression] that was identified
+    pub compression: Compression,
+    /// [Format] that was identified
+    pub format: Format,
+}
+
+/// Determines the [Format] from (decompressed) leading bytes
+fn identify_format(head: &[u8]) -> Result<Format, Error> {
+    let mut reader = BufReader::new(head);
+    let mut line = String::new();
+
+    // only the first line is to be considered:
+    // a binary blob will either fail right here or not match any known label.
+    if reader.read_line(&mut line).is_err() {
+        return Err(Error::UnknownFormat);
+    }
+
+    Format::from_first_line(&line).ok_or(Error::UnknownFormat)
+}
+
+/// Identifies [Compression] and [Format] of the file located at [Path],
+/// by reading its content. The file name does not contribute.
+pub fn sniff(path: &Path) -> Result<Sniffed, Error> {
+    let head = decompress::head(path, Compression::None, SNIFF_SIZE)?;
+
+    let compression = Compression::from_magic(&head);
+
+    let format = match compression {
+        Compression::None => identify_format(&head)?,
+        compression => {
+            let head = decompress::head(path, compression, SNIFF_SIZE)?;
+            identify_format(&head)?
+        },
+    };
+
+    Ok(Sniffed {
+        compression,
+        format,
+    })
+}
@@ -0,0 +1,172 @@
+//! Per station grouping of input products
+use std::path::{Path, PathBuf};
+
+use gnss_qc::prelude::{QcContext, Rinex};
+use log::Level;
+
+use crate::{
+    loader::Product,
+    preprocessing::PreprocessingReport,
+    summary::{self, InputFile},
+};
+
+/// One station (or receiver) and its products
+pub struct Station {
+    /// Station name (marker name, or receiver) when it could be determined
+    pub name: Option<String>,
+    /// Products of this station, and all shared products
+    pub ctx: QcContext,
+    /// Files that were loaded into this station
+    pub inputs: Vec<InputFile>,
+    /// Events detected while preprocessing this station
+    pub preprocessing: PreprocessingReport,
+    /// Warnings and errors emitted while loading and preprocessing this station
+    pub logs: Vec<(Level, String)>,
+}
+
+impl Station {
+    fn new(name: Option<String>) -> Self {
+        Self {
+            name,
+            ctx: QcContext::new(),
+            inputs: Vec::new(),
+            preprocessing: PreprocessingReport::default(),
+            logs: Vec::new(),
+        }
+    }
+}
+
+/// Groups input products per station.
+/// Observation and Meteo RINEX are tied to one station, identified by
+/// its marker name (or receiver when the marker is not defined).
+/// All other products (NAV, CLK, SP3, IONEX, ANTEX..) are shared
+/// and stacked into every station.
+#[derive(Default)]
+pub struct Stations {
+    /// Detected stations, in order of appearance
+    stations: Vec<Station>,
+    /// Shared products, stacked into every station when we're done
+    shared: Vec<(PathBuf, Product)>,
+    /// Station the last product was stacked into
+    last: Option<usize>,
+    /// Warnings and errors that apply to every station
+    shared_logs: Vec<(Level, String)>,
+}
+
+/// Returns the station this [Rinex] is tied to,
+/// or None when this is a shared product.
+fn station_key(rinex: &Rinex) -> Option<Option<String>> {
+    if !rinex.is_observation_rinex() && !rinex.is_meteo_rinex() {
+        return None;
+    }
+
+    let marker = rinex
+        .header
+        .geodetic_marker
+        .as_ref()
+        .map(|marker| marker.name.trim().to_uppercase())
+        .filter(|name| !name.is_empty());
+
+    let receiver = rinex
+        .header
+        .rcvr
+        .as_ref()
+        .map(|rcvr| format!("{}-{}", rcvr.model.trim(), rcvr.sn.trim()).to_uppercase())
+        .filter(|name| name != "-");
+
+    Some(marker.or(receiver))
+}
+
+impl Stations {
+    /// Stacks one [Product], either into its station or as a shared product.
+    pub fn stack(&mut self, path: &Path, product: Product) -> Result<(), String> {
+        let key = match &product {
+            Product::Rinex(rinex) => station_key(rinex),
+            Product::SP3(_) => None,
+        };
+
+        match key {
+            Some(name) => {
+                let index = match self.stations.iter().position(|s| s.name == name) {
+                    Some(index) => index,
+                    None => {
+                        if let Some(name) = &name {
+                            debug!("new station: {}", name);
+                        }
+                        self.stations.push(Station::new(name));
+                        self.stations.len() - 1
+                    },
+                };
+
+                self.last = Some(index);
+
+                let station = &mut self.stations[index];
+                let input = InputFile::new(path, product.product_type());
+
+                product.load(&mut station.ctx, path)?;
+                station.inputs.push(input);
+                Ok(())
+            },
+            None => {
+                self.shared.push((path.to_path_buf(), product));
+                Ok(())
+            },
+        }
+    }
+
+    /// Attributes the warnings and errors emitted since last call to the station
+    /// the last product was stacked into. All others (input selection, shared products,
+    /// files that could not be parsed) apply to every station.
+    pub fn collect_logs(&mut self) {
+        let mut logs = summary::take_logs();
+
+        match self.last.take() {
+            Some(index) => self.stations[index].logs.append(&mut logs),
+            None => self.shared_logs.append(&mut logs),
+        }
+    }
+
+    /// Stacks the shared products into every station, and returns all stations.
+    /// There is always at least one station, even when no Observation
+    /// nor Meteo RINEX was loaded.
+    pub fn finalize(mut self) -> Vec<Station> {
+        self.collect_logs();
+
+        let mut stations = self.stations;
+
+        if stations.is_empty() {
+            stations.push(Station::new(None));
+        }
+
+        if stations.len() > 1 {
+            info!(
+                "{} distinct stations: {}",
+                stations.len(),
+                stations
+                    .iter()
+                    .map(|s| s.name.as_deref().unwrap_or("unknown"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        for station in stations.iter_mut() {
+            let mut logs = self.shared_logs.clone();
+            logs.append(&mut station.logs);
+            station.logs = logs;
+
+            for (path, product) in self.shared.iter() {
+                let input = InputFile::new(path, product.product_type());
+
+                match product.clone().load(&mut station.ctx, path) {
+                    Ok(_) => station.inputs.push(input),
+                    Err(e) => warn!("\"{}\" skipped: {}", path.display(), e),
+                }
+            }
+
+            station.logs.append(&mut summary::take_logs());
+        }
+
+        stations
+    }
+}
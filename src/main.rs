@@ -4,36 +4,40 @@
 
 mod cli; // command line interface
 mod fops; // file operations
+mod loader; // input products loader
 mod preprocessing; // preprocessing
 mod report; // custom reports
+mod summary; // run summary
 
 #[cfg(feature = "ppp")]
 mod positioning; // post processed positioning
 
 use report::Report;
 
-use preprocessing::context_preprocessing;
+use preprocessing::PreprocessingPipeline;
 
-use gnss_qc::prelude::QcContext;
 use rinex::prelude::{FormattingError as RinexFormattingError, ParsingError as RinexParsingError};
 
-use std::path::Path;
-use walkdir::WalkDir;
-
 extern crate gnss_rs as gnss;
 
 use rinex::prelude::qc::MergeError;
 
 use cli::{Cli, Context, Workspace};
+use loader::{Inputs, Station, Stations};
+use summary::{Recorder, Summary};
 
 #[cfg(feature = "csv")]
 use csv::Error as CsvError;
 
-#[cfg(feature = "ppp")]
-use gnss_qc::prelude::QcExtraPage;
+use gnss_qc::prelude::{Epoch, QcExtraPage};
 
 use env_logger::{Builder, Target};
 
+use std::{
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
 #[macro_use]
 extern crate log;
 
@@ -41,8 +45,22 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("i/o error")]
+    #[error("i/o error: {0}")]
     StdioError(#[from] std::io::Error),
+    #[error("no input product could be loaded")]
+    MissingInput,
+    #[error("failed to load \"{0}\": {1}")]
+    InputLoading(String, loader::Error),
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    #[error("failed to generate \"{0}\": {1}")]
+    OutputFormatting(String, RinexFormattingError),
+    #[error("failed to generate \"{0}\": {1}")]
+    Sp3Formatting(String, String),
+    #[error("invalid configuration: {0}")]
+    Configuration(String),
+    #[error("preprocessing: {0}")]
+    Preprocessing(#[from] preprocessing::Error),
     #[error("missing OBS RINEX")]
     MissingObservationRinex,
     #[error("RINEX parsing error: {0}")]
@@ -59,6 +77,8 @@ pub enum Error {
     MissingMeteoRinex,
     #[error("missing Clock RINEX")]
     MissingClockRinex,
+    #[error("missing SP3")]
+    MissingSP3,
     #[cfg(feature = "csv")]
     #[error("csv export error")]
     CsvError(#[from] CsvError),
@@ -67,171 +87,228 @@ pub enum Error {
     PositioningSolverError(#[from] positioning::Error),
 }
 
-/// Parses and preprepocess all files passed by User
+/// Process exit code: invalid command line or configuration
+pub const EXIT_CONFIGURATION: u8 = 2;
+/// Process exit code: missing or invalid input data
+pub const EXIT_INPUT: u8 = 3;
+/// Process exit code: output product generation failure
+pub const EXIT_FORMATTING: u8 = 4;
+/// Process exit code: navigation solver failure
+pub const EXIT_SOLVER: u8 = 5;
+
+impl Error {
+    /// Returns the process exit code for this [Error].
+    /// Refer to documentation/ExitCodes.md.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Configuration(_) | Self::Preprocessing(_) => EXIT_CONFIGURATION,
+            Self::MissingInput
+            | Self::InputLoading(_, _)
+            | Self::InvalidInput(_)
+            | Self::MissingObservationRinex
+            | Self::MissingNavigationRinex
+            | Self::MissingIONEX
+            | Self::MissingMeteoRinex
+            | Self::MissingClockRinex
+            | Self::MissingSP3
+            | Self::RinexParsing(_)
+            | Self::Merge(_) => EXIT_INPUT,
+            Self::StdioError(_)
+            | Self::RinexFormatting(_)
+            | Self::OutputFormatting(_, _)
+            | Self::Sp3Formatting(_, _) => EXIT_FORMATTING,
+            #[cfg(feature = "csv")]
+            Self::CsvError(_) => EXIT_FORMATTING,
+            #[cfg(feature = "ppp")]
+            Self::PositioningSolverError(e) => match e {
+                positioning::Error::ConfigurationRead(_)
+                | positioning::Error::ConfigurationParsing(_) => EXIT_CONFIGURATION,
+                #[cfg(not(feature = "cggtts"))]
+                positioning::Error::CggttsNotAvailable => EXIT_CONFIGURATION,
+                #[cfg(feature = "cggtts")]
+                positioning::Error::MissingReferencePoint => EXIT_CONFIGURATION,
+                #[cfg(not(feature = "gpx"))]
+                positioning::Error::GpxNotAvailable => EXIT_CONFIGURATION,
+                #[cfg(not(feature = "kml"))]
+                positioning::Error::KmlNotAvailable => EXIT_CONFIGURATION,
+                positioning::Error::MissingObservationRinex
+                | positioning::Error::MissingNavigationRinex
+                | positioning::Error::InvalidInput(_) => EXIT_INPUT,
+                #[cfg(feature = "cggtts")]
+                positioning::Error::CggttsFormatting(_) => EXIT_FORMATTING,
+                positioning::Error::StdioError(_) | positioning::Error::PPPPost(_) => {
+                    EXIT_FORMATTING
+                },
+                positioning::Error::SolverError(_) | positioning::Error::NoSolutions => EXIT_SOLVER,
+            },
+        }
+    }
+}
+
+/// Parses and preprepocess all files passed by User.
+/// Returns one context per station.
 fn user_data_parsing(
     cli: &Cli,
-    single_files: Vec<&String>,
-    directories: Vec<&String>,
-    max_depth: usize,
+    pipeline: &PreprocessingPipeline,
+    inputs: Inputs,
     is_rover: bool,
-) -> QcContext {
-    let mut ctx = QcContext::new();
+) -> Result<Vec<Station>, Error> {
+    let paths = inputs.paths();
 
-    if cli.jpl_bpc_update() {
-        #[cfg(not(feature = "ppp"))]
-        error!("--jpl-bpc only applies along PPP/PVT solver options");
+    let total = paths.len();
+    let mut stations = Stations::default();
+    let loaded = loader::load_files(&mut stations, &paths, cli.jobs(), !cli.quiet());
+    info!("{}/{} files loaded", loaded, total);
 
-        #[cfg(feature = "ppp")]
-        ctx.with_jpl_bpc()
-            .unwrap_or_else(|e| panic!("Upgrade to high precision context failed: {}", e));
-    }
+    let mut stations = stations.finalize();
 
-    // recursive dir loader
-    for dir in directories.iter() {
-        let walkdir = WalkDir::new(dir).max_depth(max_depth);
-        for entry in walkdir.into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-
-            if !path.is_dir() {
-                let extension = path
-                    .extension()
-                    .unwrap_or_else(|| {
-                        panic!("failed to determine file extension: \"{}\"", path.display())
-                    })
-                    .to_string_lossy()
-                    .to_string();
-
-                if extension == "gz" {
-                    match ctx.load_gzip_rinex_file(path) {
-                        Ok(_) => {
-                            info!("RINEX file loaded: \"{}\"", path.display());
-                        },
-                        Err(_) => match ctx.load_gzip_sp3_file(path) {
-                            Ok(_) => {
-                                info!("SP3 file loaded: \"{}\"", path.display());
-                            },
-                            Err(_) => {
-                                panic!("File format not recognized!");
-                            },
-                        },
-                    }
-                } else {
-                    match ctx.load_rinex_file(path) {
-                        Ok(_) => {
-                            info!("RINEX file loaded: \"{}\"", path.display());
-                        },
-                        Err(_) => match ctx.load_sp3_file(path) {
-                            Ok(_) => {
-                                info!("SP3 file loaded: \"{}\"", path.display());
-                            },
-                            Err(_) => {
-                                panic!("File format not recognized!");
-                            },
-                        },
-                    }
-                }
-            }
-        }
-    }
+    for station in stations.iter_mut() {
+        let ctx = &mut station.ctx;
 
-    // load individual files
-    for fp in single_files.iter() {
-        let path = Path::new(fp);
+        if cli.jpl_bpc_update() {
+            #[cfg(not(feature = "ppp"))]
+            error!("--jpl-bpc only applies along PPP/PVT solver options");
 
-        let extension = path
-            .extension()
-            .unwrap_or_else(|| panic!("failed to determine file extension: \"{}\"", path.display()))
-            .to_string_lossy()
-            .to_string();
+            #[cfg(feature = "ppp")]
+            ctx.with_jpl_bpc().map_err(|e| {
+                Error::InvalidInput(format!("upgrade to high precision context failed: {}", e))
+            })?;
+        }
 
-        if extension == "gz" {
-            match ctx.load_gzip_rinex_file(path) {
-                Ok(_) => {
-                    info!("RINEX file loaded: \"{}\"", path.display());
-                },
-                Err(_) => match ctx.load_gzip_sp3_file(path) {
-                    Ok(_) => {
-                        info!("SP3 file loaded: \"{}\"", path.display());
-                    },
-                    Err(_) => {
-                        panic!("File format not recognized!");
-                    },
-                },
-            }
+        // Preprocessing
+        let rx_orbit = if pipeline.requires_rx_orbit() {
+            cli.reference_rx_orbit(ctx)?
         } else {
-            match ctx.load_rinex_file(path) {
-                Ok(_) => {
-                    info!("RINEX file loaded: \"{}\"", path.display());
-                },
-                Err(_) => match ctx.load_sp3_file(path) {
-                    Ok(_) => {
-                        info!("SP3 file loaded: \"{}\"", path.display());
-                    },
-                    Err(_) => {
-                        panic!("File format not recognized!");
-                    },
-                },
-            }
+            None
+        };
+
+        station.preprocessing = pipeline.apply_context(ctx, rx_orbit.as_ref());
+
+        match cli.matches.subcommand() {
+            Some(("rtk", _)) => {
+                if is_rover {
+                    debug!("ROVER Dataset: {:?}", ctx);
+                } else {
+                    error!("BASE STATION Dataset: {:?}", ctx);
+                }
+            },
+            _ => {
+                debug!("{:?}", ctx);
+            },
         }
+
+        station.logs.append(&mut summary::take_logs());
     }
 
-    // Preprocessing
-    context_preprocessing(&mut ctx, cli);
+    Ok(stations)
+}
 
-    match cli.matches.subcommand() {
-        Some(("rtk", _)) => {
-            if is_rover {
-                debug!("ROVER Dataset: {:?}", ctx);
-            } else {
-                error!("BASE STATION Dataset: {:?}", ctx);
-            }
-        },
-        _ => {
-            debug!("{:?}", ctx);
-        },
+/// Workspace name for this [Station]. When several stations were loaded,
+/// each of them is processed in a dedicated subfolder named after the station.
+fn session_name(station: &Station, multi_station: bool) -> Result<String, Error> {
+    match (&station.name, multi_station) {
+        (Some(name), true) => Ok(name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()),
+        _ => Context::context_stem(&station.ctx),
     }
-
-    ctx
 }
 
-pub fn main() -> Result<(), Error> {
+pub fn main() -> ExitCode {
     let mut builder = Builder::from_default_env();
 
-    builder
+    let logger = builder
         .target(Target::Stdout)
         .format_timestamp_secs()
         .format_module_path(false)
-        .init();
+        .build();
+
+    Recorder::init(logger);
+
+    match session() {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::from(e.exit_code())
+        },
+    }
+}
+
+/// Runs the complete session
+fn session() -> Result<(), Error> {
+    let start = Instant::now();
+    let start_time = Epoch::now().ok();
 
     /*
      * Build context defined by user
      *   Parse all data, determine other useful information
      */
     let cli = Cli::new();
-    let max_recursive_depth = cli.recursive_depth();
+
+    // validated prior loading anything
+    let pipeline = PreprocessingPipeline::from_cli(&cli)?;
+
+    for step in pipeline.steps() {
+        info!("preprocessing: {}", step);
+    }
 
     // User (ROVER) Data parsing
-    let mut data_ctx = user_data_parsing(
-        &cli,
-        cli.rover_files(),
-        cli.rover_directories(),
-        max_recursive_depth,
-        true,
-    );
+    let stations = user_data_parsing(&cli, &pipeline, cli.rover_inputs(), true)?;
+    let multi_station = stations.len() > 1;
+    let loading = start.elapsed();
 
-    let ctx_stem = Context::context_stem(&mut data_ctx);
+    let mut result = Ok(());
+
+    // each station is processed separately
+    for station in stations {
+        let session = session_name(&station, multi_station)?;
+
+        if let Err(e) = run(&cli, &pipeline, station, &session, start_time, loading) {
+            error!("{}: {}", session, e);
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+
+    result
+}
+
+/// Runs the selected opmode on this [Station],
+/// and possibly generates the run summary.
+fn run(
+    cli: &Cli,
+    pipeline: &PreprocessingPipeline,
+    station: Station,
+    session: &str,
+    start_time: Option<Epoch>,
+    loading: Duration,
+) -> Result<(), Error> {
+    let data_ctx = station.ctx;
+    let mut logs = station.logs;
 
     // Input context
     let mut ctx = Context {
-        name: ctx_stem.clone(),
+        name: session.to_string(),
+        summary: Summary::new(
+            session,
+            cli.opmode(),
+            station.inputs,
+            pipeline.steps().to_vec(),
+            start_time,
+            loading,
+        ),
+        preprocessing: pipeline.clone(),
+        preprocessing_report: station.preprocessing,
 
         #[cfg(feature = "ppp")]
         rx_orbit: {
             // possible reference point
             if let Some(rx_orbit) = data_ctx.reference_rx_orbit() {
-                let (lat_ddeg, long_ddeg, alt_km) = rx_orbit
-                    .latlongalt()
-                    .unwrap_or_else(|e| panic!("latlongalt - physical error: {}", e));
+                let (lat_ddeg, long_ddeg, alt_km) = rx_orbit.latlongalt().map_err(|e| {
+                    Error::InvalidInput(format!("reference point - physical error: {}", e))
+                })?;
 
                 info!(
                     "reference point identified: latitude={:.5}°, longitude={:.5}° altitude={:.5}m",
@@ -247,36 +324,54 @@ pub fn main() -> Result<(), Error> {
         },
 
         data: data_ctx,
-        quiet: cli.matches.get_flag("quiet"),
-        workspace: Workspace::new(&ctx_stem, &cli),
+        quiet: cli.quiet(),
+        workspace: Workspace::new(session, cli)?,
     };
 
+    let result = process(cli, &mut ctx);
+
+    // warnings & errors of this session
+    logs.append(&mut summary::take_logs());
+
+    if cli.run_summary() {
+        if let Err(e) = ctx.summary.write(&ctx.workspace.root, &result, logs) {
+            error!("failed to generate run summary: {}", e);
+        }
+    }
+
+    result
+}
+
+/// Deploys the selected opmode
+fn process(cli: &Cli, ctx: &mut Context) -> Result<(), Error> {
     // ground reference point
     #[cfg(feature = "ppp")]
     match ctx.rx_orbit {
         Some(_) => {
             if let Some(obs_rinex) = ctx.data.observation() {
                 if let Some(t0) = obs_rinex.first_epoch() {
-                    if let Some(rx_orbit) = cli.manual_rx_orbit(t0, ctx.data.earth_cef) {
-                        let (lat_ddeg, long_ddeg, alt_km) = rx_orbit
-                            .latlongalt()
-                            .unwrap_or_else(|e| panic!("latlongalt - physical error: {}", e));
+                    if let Some(rx_orbit) = cli.manual_rx_orbit(t0, ctx.data.earth_cef)? {
+                        let (lat_ddeg, long_ddeg, alt_km) = rx_orbit.latlongalt().map_err(|e| {
+                            Error::Configuration(format!("reference point - physical error: {}", e))
+                        })?;
 
                         info!("reference point manually overwritten: latitude={:.5}°, longitude={:.5}°, altitude={:.5}m", lat_ddeg, long_ddeg, alt_km * 1.0E3);
                         ctx.rx_orbit = Some(rx_orbit);
                     }
                 }
-            } else {
-                panic!("manual definition of a reference point requires OBS RINEX");
+            } else if cli.manual_rx_orbit_defined() {
+                return Err(Error::Configuration(
+                    "manual definition of a reference point requires OBS RINEX".to_string(),
+                ));
             }
         },
         None => {
             if let Some(obs_rinex) = ctx.data.observation() {
                 if let Some(t0) = obs_rinex.first_epoch() {
-                    if let Some(rx_orbit) = cli.manual_rx_orbit(t0, ctx.data.earth_cef) {
-                        let (lat_ddeg, long_ddeg, alt_km) = rx_orbit
-                            .latlongalt()
-                            .unwrap_or_else(|e| panic!("latlongalt - physical error: {}", e));
+                    if let Some(rx_orbit) = cli.manual_rx_orbit(t0, ctx.data.earth_cef)? {
+                        let (lat_ddeg, long_ddeg, alt_km) = rx_orbit.latlongalt().map_err(|e| {
+                            Error::Configuration(format!("reference point - physical error: {}", e))
+                        })?;
 
                         info!("reference point manually defined: latitude={:.5}°, longitude={:.5}°, altitude={:.5}m", lat_ddeg, long_ddeg, alt_km * 1.0E3);
                         ctx.rx_orbit = Some(rx_orbit);
@@ -304,8 +399,11 @@ pub fn main() -> Result<(), Error> {
         }
     }
 
+    // preprocessing events
+    ctx.preprocessing_report
+        .write_csv(&ctx.workspace, &ctx.summary)?;
+
     // Exclusive opmodes to follow
-    #[cfg(feature = "ppp")]
     let mut extra_pages = Vec::<QcExtraPage>::new();
 
     match cli.matches.subcommand() {
@@ -313,37 +411,40 @@ pub fn main() -> Result<(), Error> {
         // Users need to re-run (re execute) on previously generated data
         // to perform their analysis.
         Some(("filegen", submatches)) => {
-            fops::filegen(&ctx, &cli.matches, submatches)?;
+            fops::filegen(ctx, &cli.matches, submatches)?;
             return Ok(());
         },
         Some(("merge", submatches)) => {
-            fops::merge(&ctx, &cli, submatches)?;
+            fops::merge(ctx, cli, submatches)?;
             return Ok(());
         },
         Some(("split", submatches)) => {
-            fops::split(&ctx, submatches)?;
+            fops::split(ctx, submatches)?;
             return Ok(());
         },
         Some(("tbin", submatches)) => {
-            fops::time_binning(&ctx, &cli.matches, submatches)?;
+            fops::time_binning(ctx, &cli.matches, submatches)?;
             return Ok(());
         },
         Some(("cbin", submatches)) => {
-            fops::constell_timescale_binning(&ctx, submatches)?;
+            fops::constell_timescale_binning(ctx, submatches)?;
             return Ok(());
         },
         Some(("diff", submatches)) => {
-            fops::diff(&ctx, &cli, submatches)?;
-            return Ok(());
+            // differences statistics continue to the report
+            match fops::diff(ctx, cli, submatches)? {
+                Some(chapter) => extra_pages.push(chapter),
+                None => return Ok(()),
+            }
         },
         #[cfg(feature = "ppp")]
         Some(("ppp", submatches)) => {
-            let chapter = positioning::precise_positioning(&cli, &ctx, false, submatches)?;
+            let chapter = positioning::precise_positioning(cli, ctx, false, submatches)?;
             extra_pages.push(chapter);
         },
         #[cfg(feature = "ppp")]
         Some(("rtk", submatches)) => {
-            let chapter = positioning::precise_positioning(&cli, &ctx, true, submatches)?;
+            let chapter = positioning::precise_positioning(cli, ctx, true, submatches)?;
             extra_pages.push(chapter);
         },
         _ => {},
@@ -352,20 +453,23 @@ pub fn main() -> Result<(), Error> {
     // report
     let cfg = cli.qc_config();
 
-    let mut report = Report::new(&cli, &ctx, cfg);
+    let mut report = Report::new(cli, ctx, cfg);
+
+    for extra in ctx.preprocessing_report.extra_pages() {
+        report.customize(extra);
+    }
 
-    #[cfg(feature = "ppp")]
     for extra in extra_pages {
         // customization
         report.customize(extra);
     }
 
     // synthesis
-    report.generate(&cli, &ctx)?;
+    report.generate(cli, ctx)?;
 
     if !ctx.quiet {
         ctx.workspace.open_with_web_browser();
     }
 
     Ok(())
-} // main
+}
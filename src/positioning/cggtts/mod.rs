@@ -93,15 +93,20 @@ pub fn resolve<'a, 'b, CK: ClockStateProvider, O: OrbitSource, B: Bias, T: Absol
     let obs_data = ctx
         .data
         .observation()
-        .expect("RNX2CGGTTS requires OBS RINEX");
+        .ok_or(PositioningError::MissingObservationRinex)?;
 
     let t0 = obs_data
         .first_epoch()
-        .expect("failed to determine first epoch, empty observations?");
+        .ok_or(PositioningError::InvalidInput(
+            "failed to determine first epoch, empty observations?".to_string(),
+        ))?;
 
-    let sampling_period = obs_data
-        .dominant_sampling_interval()
-        .expect("RNX2CGGTTS requires steady GNSS observations");
+    let sampling_period =
+        obs_data
+            .dominant_sampling_interval()
+            .ok_or(PositioningError::InvalidInput(
+                "RNX2CGGTTS requires steady GNSS observations".to_string(),
+            ))?;
 
     // scheduling
     let mut past_t = t0;
@@ -242,6 +247,14 @@ pub fn resolve<'a, 'b, CK: ClockStateProvider, O: OrbitSource, B: Bias, T: Absol
             } // collecting
         } // new epoch
 
+        // masked outliers are not used
+        if ctx
+            .preprocessing_report
+            .is_masked(t, signal.sv, &signal.observable)
+        {
+            continue;
+        }
+
         let carrier = Carrier::from_observable(signal.sv.constellation, &signal.observable);
 
         if carrier.is_err() {
@@ -1,5 +1,5 @@
 //! CGGTTS track formation and post processing
-use crate::cli::Context;
+use crate::{cli::Context, positioning::Error};
 use clap::ArgMatches;
 
 use itertools::Itertools;
@@ -7,11 +7,7 @@ use itertools::Itertools;
 use cggtts::prelude::{Header, Track, CGGTTS};
 
 /// CGGTTS solutions post processing
-pub fn post_process(
-    ctx: &Context,
-    tracks: &Vec<Track>,
-    matches: &ArgMatches,
-) -> std::io::Result<()> {
+pub fn post_process(ctx: &Context, tracks: &Vec<Track>, matches: &ArgMatches) -> Result<(), Error> {
     // let obs_data = ctx.data.observation().unwrap();
 
     let mut header = Header::default();
@@ -39,7 +35,7 @@ pub fn post_process(
     if let Some(custom) = matches.get_one::<String>("agency") {
         header = header.with_station(custom);
     } else {
-        let stem = Context::context_stem(&ctx.data);
+        let stem = Context::context_stem(&ctx.data).unwrap_or_default();
         let value = if let Some(index) = stem.find('_') {
             stem[..index].to_string()
         } else {
@@ -68,7 +64,9 @@ pub fn post_process(
 
         cggtts
             .to_file(&fullpath)
-            .unwrap_or_else(|e| panic!("CGGTTS formatting error: {}", e));
+            .map_err(|e| Error::CggttsFormatting(e.to_string()))?;
+
+        ctx.summary.output(&fullpath);
     }
 
     Ok(())
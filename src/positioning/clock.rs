@@ -1,6 +1,6 @@
 use crate::{
     cli::Context,
-    positioning::{Buffer, CenteredDataPoints, CenteredSnapshot, EphemerisSource},
+    positioning::{Buffer, CenteredDataPoints, CenteredSnapshot, EphemerisSource, Error},
 };
 
 use std::{cell::RefCell, collections::HashMap};
@@ -44,7 +44,7 @@ impl ClockStateProvider for Clock<'_, '_> {
 }
 
 impl<'a, 'b> Clock<'a, 'b> {
-    pub fn new(ctx: &'a Context, eph: &'a RefCell<EphemerisSource<'b>>) -> Self {
+    pub fn new(ctx: &'a Context, eph: &'a RefCell<EphemerisSource<'b>>) -> Result<Self, Error> {
         let has_precise = ctx.data.clock().is_some();
 
         let mut s = Self {
@@ -52,10 +52,9 @@ impl<'a, 'b> Clock<'a, 'b> {
             has_precise,
             eos: false,
             sampling_period: if let Some(clk) = ctx.data.clock() {
-                let dt = clk
-                    .dominant_sampling_interval()
-                    .expect("Invalid clock RINEX: undefined sampling interval");
-                dt
+                clk.dominant_sampling_interval().ok_or(Error::InvalidInput(
+                    "invalid Clock RINEX: undefined sampling interval".to_string(),
+                ))?
             } else {
                 Duration::default()
             },
@@ -88,7 +87,7 @@ impl<'a, 'b> Clock<'a, 'b> {
             s.consume_many(128);
         }
 
-        s
+        Ok(s)
     }
 
     fn consume_one(&mut self) {
@@ -1,4 +1,4 @@
-use crate::cli::Context;
+use crate::{cli::Context, positioning::Error};
 use rinex::navigation::Ephemeris;
 use rinex::prelude::{Epoch, SV};
 use std::collections::HashMap;
@@ -13,11 +13,12 @@ pub struct EphemerisSource<'a> {
 
 impl<'a> EphemerisSource<'a> {
     /// Builds new [EphemerisSource] from [Context]
-    pub fn from_ctx(ctx: &'a Context) -> Self {
+    pub fn from_ctx(ctx: &'a Context) -> Result<Self, Error> {
+        // Navigation RINEX is currently mandatory
         let brdc = ctx
             .data
             .brdc_navigation()
-            .expect("Navigation RINEX is currently mandatory..");
+            .ok_or(Error::MissingNavigationRinex)?;
 
         info!("Ephemeris data source created.");
 
@@ -33,7 +34,7 @@ impl<'a> EphemerisSource<'a> {
         };
 
         s.consume_many(32); // fill in with some data
-        s
+        Ok(s)
     }
 
     /// Consume one entry from [Iterator]
@@ -79,6 +79,31 @@ pub enum Error {
     StdioError(#[from] std::io::Error),
     #[error("post process error")]
     PPPPost(#[from] PPPPostError),
+    #[error("failed to read configuration: {0}")]
+    ConfigurationRead(std::io::Error),
+    #[error("failed to parse configuration: {0}")]
+    ConfigurationParsing(#[from] serde_json::Error),
+    #[cfg(not(feature = "cggtts"))]
+    #[error("--cggtts option not available: compile with cggtts option")]
+    CggttsNotAvailable,
+    #[cfg(feature = "cggtts")]
+    #[error("cggtts needs a reference point (x0, y0, z0): see --help")]
+    MissingReferencePoint,
+    #[error("positioning requires Observation RINEX")]
+    MissingObservationRinex,
+    #[error("positioning requires Navigation RINEX")]
+    MissingNavigationRinex,
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+    #[cfg(not(feature = "gpx"))]
+    #[error("--gpx option not available: compile with gpx option")]
+    GpxNotAvailable,
+    #[cfg(not(feature = "kml"))]
+    #[error("--kml option not available: compile with kml option")]
+    KmlNotAvailable,
+    #[cfg(feature = "cggtts")]
+    #[error("CGGTTS formatting error: {0}")]
+    CggttsFormatting(String),
 }
 
 /// Converts [RTKCarrier] to [Carrier]
@@ -245,18 +270,16 @@ pub fn precise_positioning(
     // Load custom configuration script, or Default
     let cfg = match matches.get_one::<String>("cfg") {
         Some(fp) => {
-            let content = read_to_string(fp)
-                .unwrap_or_else(|e| panic!("failed to read configuration: {}", e));
+            let content = read_to_string(fp).map_err(Error::ConfigurationRead)?;
 
-            let cfg: Config = serde_json::from_str(&content)
-                .unwrap_or_else(|e| panic!("failed to parse configuration: {}", e));
+            let cfg: Config = serde_json::from_str(&content)?;
 
             /*
              * CGGTTS special case
              */
             #[cfg(not(feature = "cggtts"))]
             if matches.get_flag("cggtts") {
-                panic!("--cggtts option not available: compile with cggtts option");
+                return Err(Error::CggttsNotAvailable);
             }
 
             info!("Using custom solver configuration: {:#?}", cfg);
@@ -272,7 +295,7 @@ pub fn precise_positioning(
              */
             #[cfg(not(feature = "cggtts"))]
             if matches.get_flag("cggtts") {
-                panic!("--cggtts option not available: compile with cggtts option");
+                return Err(Error::CggttsNotAvailable);
             }
 
             info!("Using {:?} default preset: {:#?}", method, cfg);
@@ -280,17 +303,23 @@ pub fn precise_positioning(
         },
     };
 
+    #[cfg(not(feature = "gpx"))]
+    if matches.get_flag("gpx") {
+        return Err(Error::GpxNotAvailable);
+    }
+
+    #[cfg(not(feature = "kml"))]
+    if matches.get_flag("kml") {
+        return Err(Error::KmlNotAvailable);
+    }
+
     /* Verify requirements and print helpful comments */
-    assert!(
-        ctx.data.observation().is_some(),
-        "Positioning requires Observation RINEX"
-    );
+    if ctx.data.observation().is_none() {
+        return Err(Error::MissingObservationRinex);
+    }
 
-    if !is_rtk {
-        assert!(
-            ctx.data.brdc_navigation().is_some(),
-            "Positioning requires Navigation RINEX"
-        );
+    if !is_rtk && ctx.data.brdc_navigation().is_none() {
+        return Err(Error::MissingNavigationRinex);
     }
 
     if let Some(obs_rinex) = ctx.data.observation() {
@@ -327,9 +356,9 @@ pub fn precise_positioning(
     info!("Using {:?} method", cfg.method);
 
     // create data providers
-    let eph = RefCell::new(EphemerisSource::from_ctx(ctx));
+    let eph = RefCell::new(EphemerisSource::from_ctx(ctx)?);
 
-    let clocks = Clock::new(&ctx, &eph);
+    let clocks = Clock::new(&ctx, &eph)?;
     let time = Time::new(&ctx);
     let orbits = Orbits::new(&ctx, &eph);
 
@@ -339,10 +368,8 @@ pub fn precise_positioning(
     #[cfg(feature = "cggtts")]
     if matches.get_flag("cggtts") {
         if ctx.rx_orbit.is_none() {
-            panic!(
-                "cggtts needs a reference point (x0, y0, z0).
-If your dataset does not describe one, you can manually describe one, see --help."
-            );
+            error!("If your dataset does not describe one, you can manually describe one");
+            return Err(Error::MissingReferencePoint);
         }
     }
 
@@ -44,6 +44,14 @@ pub fn resolve<'a, 'b, CK: ClockStateProvider, O: OrbitSource, B: Bias, T: Absol
     let mut remote_observations = Vec::<Observation>::new();
 
     for (t, signal) in obs_data.signal_observations_sampling_ok_iter() {
+        // masked outliers are not used
+        if ctx
+            .preprocessing_report
+            .is_masked(t, signal.sv, &signal.observable)
+        {
+            continue;
+        }
+
         let carrier = Carrier::from_observable(signal.sv.constellation, &signal.observable);
 
         if carrier.is_err() {
@@ -43,10 +43,15 @@ pub fn post_process(
     solutions: &BTreeMap<Epoch, PVTSolution>,
     matches: &ArgMatches,
 ) -> Result<(), Error> {
+    // --gpx and --kml availability is verified prior solving
+    #[cfg(not(any(feature = "gpx", feature = "kml")))]
+    let _ = matches;
+
     /*
      * Generate txt, GPX, KML..
      */
-    let mut fd = ctx.workspace.create_file("Solutions.csv");
+    let mut fd = ctx.workspace.create_file("Solutions.csv")?;
+    ctx.summary.output(ctx.workspace.root.join("Solutions.csv"));
 
     #[cfg(feature = "gpx")]
     let mut gpx_track = gpx::Track::default();
@@ -133,7 +138,9 @@ pub fn post_process(
     #[cfg(feature = "gpx")]
     if matches.get_flag("gpx") {
         let prefix = ctx.name.clone();
-        let fd = ctx.workspace.create_file(&format!("{}.gpx", prefix));
+        let fd = ctx.workspace.create_file(&format!("{}.gpx", prefix))?;
+        ctx.summary
+            .output(ctx.workspace.root.join(format!("{}.gpx", prefix)));
 
         let mut gpx = Gpx::default();
         gpx.version = GpxVersion::Gpx11;
@@ -142,15 +149,13 @@ pub fn post_process(
         gpx.tracks.push(gpx_track);
         gpx::write(&gpx, fd)?;
     }
-    #[cfg(not(feature = "gpx"))]
-    if matches.get_flag("gpx") {
-        panic!("--gpx option is not available: compile with gpx option");
-    }
 
     #[cfg(feature = "kml")]
     if matches.get_flag("kml") {
         let prefix = ctx.name.clone();
-        let mut fd = ctx.workspace.create_file(&format!("{}.kml", prefix));
+        let mut fd = ctx.workspace.create_file(&format!("{}.kml", prefix))?;
+        ctx.summary
+            .output(ctx.workspace.root.join(format!("{}.kml", prefix)));
 
         let kmldoc = KmlDocument {
             version: KmlVersion::V23,
@@ -170,10 +175,6 @@ pub fn post_process(
         let mut writer = KmlWriter::from_writer(&mut fd);
         writer.write(&Kml::KmlDocument(kmldoc))?;
     }
-    #[cfg(not(feature = "kml"))]
-    if matches.get_flag("kml") {
-        panic!("--kml option is not available: compile with kml option");
-    }
 
     Ok(())
 }
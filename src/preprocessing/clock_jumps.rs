@@ -0,0 +1,218 @@
+//! Receiver clock jumps detection and repair
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use rinex::prelude::{obs::LliFlags, Carrier, Epoch, Observable, Rinex, SV};
+
+use crate::preprocessing::code_smoothing::matching_phase;
+
+#[cfg(feature = "csv")]
+use csv::Writer;
+
+#[cfg(feature = "csv")]
+use std::path::Path;
+
+const SPEED_OF_LIGHT_M_S: f64 = 299_792_458.0;
+
+/// Variation of the code minus phase difference, in meters,
+/// above which one SV is considered affected by a clock jump (half a millisecond).
+const JUMP_THRESHOLD_M: f64 = SPEED_OF_LIGHT_M_S * 0.5E-3;
+
+/// Jumps this close to an integer number of milliseconds are rounded
+const MS_TOLERANCE: f64 = 1.0E-2;
+
+/// What to do with detected clock jumps
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClockJumpMode {
+    /// Report clock jumps only
+    Detect,
+    /// Report clock jumps, and re-align the pseudo ranges
+    /// and receiver clock offset for continuity
+    Repair,
+}
+
+impl FromStr for ClockJumpMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "detect" => Ok(Self::Detect),
+            "repair" => Ok(Self::Repair),
+            _ => Err(format!("unknown clock jump mode \"{}\"", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ClockJumpMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Detect => write!(f, "detect"),
+            Self::Repair => write!(f, "repair"),
+        }
+    }
+}
+
+/// One receiver clock jump
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockJump {
+    /// First [Epoch] after the jump
+    pub epoch: Epoch,
+    /// Jump magnitude, in milliseconds
+    pub magnitude_ms: f64,
+    /// Number of SV the jump was observed on
+    pub satellites: usize,
+    /// True if the observations were re-aligned
+    pub repaired: bool,
+}
+
+/// Detects receiver clock jumps in this Observation [Rinex]: a clock jump shows up as
+/// a simultaneous step of the code minus phase difference on most satellites.
+/// In [ClockJumpMode::Repair], all following pseudo ranges and receiver clock offsets
+/// are re-aligned, so they are continuous and consistent with the phase.
+pub fn process(rinex: &mut Rinex, mode: ClockJumpMode) -> Vec<ClockJump> {
+    let record = match rinex.record.as_mut_obs() {
+        Some(record) => record,
+        None => return Vec::new(),
+    };
+
+    let repair = mode == ClockJumpMode::Repair;
+
+    let mut jumps = Vec::new();
+    let mut cumulated_ms = 0.0;
+
+    // code minus phase of the previous epoch, per SV and pseudo range
+    let mut previous = HashMap::<(SV, Observable), f64>::new();
+
+    for (k, v) in record.iter_mut() {
+        if !k.flag.is_ok() {
+            continue;
+        }
+
+        let mut phases = HashMap::<SV, Vec<(Carrier, Observable, f64, bool)>>::new();
+
+        for signal in v.signals.iter() {
+            if !signal.observable.is_phase_observable() {
+                continue;
+            }
+            if let Ok(carrier) =
+                Carrier::from_observable(signal.sv.constellation, &signal.observable)
+            {
+                let lli = signal
+                    .lli
+                    .map_or(false, |lli| lli.intersects(LliFlags::LOCK_LOSS));
+
+                phases.entry(signal.sv).or_default().push((
+                    carrier,
+                    signal.observable.clone(),
+                    signal.value,
+                    lli,
+                ));
+            }
+        }
+
+        let mut current = HashMap::<(SV, Observable), f64>::new();
+        let mut variations = Vec::new();
+
+        for signal in v.signals.iter() {
+            if !signal.observable.is_pseudorange_observable() {
+                continue;
+            }
+
+            let carrier =
+                match Carrier::from_observable(signal.sv.constellation, &signal.observable) {
+                    Ok(carrier) => carrier,
+                    Err(_) => continue,
+                };
+
+            let phase = phases
+                .get(&signal.sv)
+                .and_then(|phases| matching_phase(&signal.observable, carrier, phases));
+
+            if let Some((_, _, cycles, lli)) = phase {
+                let wavelength = SPEED_OF_LIGHT_M_S / carrier.frequency();
+                let code_minus_phase = signal.value - cycles * wavelength;
+
+                let key = (signal.sv, signal.observable.clone());
+
+                if !lli {
+                    if let Some(prev) = previous.get(&key) {
+                        variations.push(code_minus_phase - prev);
+                    }
+                }
+
+                current.insert(key, code_minus_phase);
+            }
+        }
+
+        previous = current;
+
+        let jumped = variations
+            .iter()
+            .filter(|dm| dm.abs() > JUMP_THRESHOLD_M)
+            .collect::<Vec<_>>();
+
+        // most satellites need to agree
+        if !jumped.is_empty() && jumped.len() * 2 > variations.len() {
+            let mean_m = jumped.iter().copied().sum::<f64>() / jumped.len() as f64;
+            let mean_ms = mean_m / SPEED_OF_LIGHT_M_S * 1.0E3;
+
+            let magnitude_ms = if (mean_ms - mean_ms.round()).abs() < MS_TOLERANCE {
+                mean_ms.round()
+            } else {
+                mean_ms
+            };
+
+            info!(
+                "{}: receiver clock jump of {:.3} ms ({} SV)",
+                k.epoch,
+                magnitude_ms,
+                jumped.len()
+            );
+
+            cumulated_ms += magnitude_ms;
+
+            jumps.push(ClockJump {
+                epoch: k.epoch,
+                magnitude_ms,
+                satellites: jumped.len(),
+                repaired: repair,
+            });
+        }
+
+        if repair && !jumps.is_empty() {
+            let offset_m = cumulated_ms * 1.0E-3 * SPEED_OF_LIGHT_M_S;
+
+            for signal in v.signals.iter_mut() {
+                if signal.observable.is_pseudorange_observable() {
+                    signal.value -= offset_m;
+                }
+            }
+
+            if let Some(clock) = &mut v.clock {
+                clock.offset_s -= cumulated_ms * 1.0E-3;
+            }
+        }
+    }
+
+    jumps
+}
+
+/// Writes detected clock jumps as CSV
+#[cfg(feature = "csv")]
+pub fn write_csv(jumps: &[ClockJump], path: &Path) -> Result<(), csv::Error> {
+    let mut w = Writer::from_path(path)?;
+    w.write_record(["Epoch", "Magnitude [ms]", "Satellites", "Repaired"])?;
+
+    for jump in jumps.iter() {
+        w.write_record(&[
+            jump.epoch.to_string(),
+            format!("{:.6}", jump.magnitude_ms),
+            jump.satellites.to_string(),
+            jump.repaired.to_string(),
+        ])?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
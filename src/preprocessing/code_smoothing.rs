@@ -0,0 +1,252 @@
+//! Carrier smoothed pseudo ranges (Hatch filter)
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use rinex::prelude::{obs::LliFlags, Carrier, Duration, Epoch, Observable, Rinex, SV};
+
+#[cfg(feature = "csv")]
+use csv::Writer;
+
+#[cfg(feature = "csv")]
+use std::path::Path;
+
+const SPEED_OF_LIGHT_M_S: f64 = 299_792_458.0;
+
+/// A data gap larger than this many sampling periods resets the filter
+const MAX_GAP_PERIODS: f64 = 10.0;
+
+/// Code smoothing settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeSmoothingOptions {
+    /// Smoothing window, in epochs
+    pub window: usize,
+}
+
+/// Code smoothing summary, of one SV and pseudo range
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmoothedCode {
+    pub sv: SV,
+    pub code: Observable,
+    /// Total number of samples
+    pub samples: usize,
+    /// Number of smoothed samples
+    pub smoothed: usize,
+    /// Number of filter resets (loss of lock, data gaps, missing phase)
+    pub resets: usize,
+    /// Sum of squared differences between raw and smoothed pseudo ranges, in m²
+    sum_squares: f64,
+}
+
+impl SmoothedCode {
+    /// RMS of the difference between raw and smoothed pseudo ranges, in meters:
+    /// this is an estimate of the code noise that was removed.
+    pub fn rms_m(&self) -> f64 {
+        if self.smoothed == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.smoothed as f64).sqrt()
+        }
+    }
+}
+
+/// Filter state of one SV and pseudo range
+struct HatchState {
+    last: Epoch,
+    phase: Observable,
+    phase_m: f64,
+    smoothed_m: f64,
+    n: usize,
+}
+
+/// Selects the phase observation that goes with this pseudo range:
+/// same carrier, preferably same tracking code (L1C for C1C).
+pub(super) fn matching_phase<'a>(
+    code: &Observable,
+    carrier: Carrier,
+    phases: &'a [(Carrier, Observable, f64, bool)],
+) -> Option<&'a (Carrier, Observable, f64, bool)> {
+    let code = code.to_string();
+    let attributes = code.get(1..).unwrap_or_default();
+
+    let mut candidates = phases.iter().filter(|(c, _, _, _)| *c == carrier);
+
+    candidates
+        .clone()
+        .find(|(_, phase, _, _)| phase.to_string().get(1..) == Some(attributes))
+        .or_else(|| candidates.next())
+}
+
+/// Replaces all pseudo ranges of this Observation [Rinex] by their carrier
+/// smoothed value (Hatch filter). The filter is reset on Loss of Lock,
+/// data gaps, or when the matching phase observation is missing.
+/// Returns the smoothing summary, per SV and pseudo range.
+pub fn process(rinex: &mut Rinex, opts: &CodeSmoothingOptions) -> Vec<SmoothedCode> {
+    let max_gap = rinex
+        .dominant_sampling_interval()
+        .map(|dt| dt * MAX_GAP_PERIODS)
+        .unwrap_or(Duration::from_hours(1.0));
+
+    let mut states = HashMap::<(SV, Observable), HatchState>::new();
+    let mut summaries = BTreeMap::<(SV, Observable), SmoothedCode>::new();
+
+    let record = match rinex.record.as_mut_obs() {
+        Some(record) => record,
+        None => return Vec::new(),
+    };
+
+    for (k, v) in record.iter_mut() {
+        if !k.flag.is_ok() {
+            continue;
+        }
+
+        let t = k.epoch;
+
+        // phase observations of this epoch
+        let mut phases = HashMap::<SV, Vec<(Carrier, Observable, f64, bool)>>::new();
+
+        for signal in v.signals.iter() {
+            if !signal.observable.is_phase_observable() {
+                continue;
+            }
+            if let Ok(carrier) =
+                Carrier::from_observable(signal.sv.constellation, &signal.observable)
+            {
+                let lli = signal
+                    .lli
+                    .map_or(false, |lli| lli.intersects(LliFlags::LOCK_LOSS));
+
+                phases.entry(signal.sv).or_default().push((
+                    carrier,
+                    signal.observable.clone(),
+                    signal.value,
+                    lli,
+                ));
+            }
+        }
+
+        for signal in v.signals.iter_mut() {
+            if !signal.observable.is_pseudorange_observable() {
+                continue;
+            }
+
+            let carrier =
+                match Carrier::from_observable(signal.sv.constellation, &signal.observable) {
+                    Ok(carrier) => carrier,
+                    Err(_) => continue,
+                };
+
+            let key = (signal.sv, signal.observable.clone());
+
+            let summary = summaries
+                .entry(key.clone())
+                .or_insert_with(|| SmoothedCode {
+                    sv: signal.sv,
+                    code: signal.observable.clone(),
+                    samples: 0,
+                    smoothed: 0,
+                    resets: 0,
+                    sum_squares: 0.0,
+                });
+
+            summary.samples += 1;
+
+            let phase = phases
+                .get(&signal.sv)
+                .and_then(|phases| matching_phase(&signal.observable, carrier, phases));
+
+            let (phase, phase_m, lli) = match phase {
+                Some((_, phase, cycles, lli)) => (
+                    phase,
+                    cycles * SPEED_OF_LIGHT_M_S / carrier.frequency(),
+                    *lli,
+                ),
+                None => {
+                    if states.remove(&key).is_some() {
+                        summary.resets += 1;
+                    }
+                    continue;
+                },
+            };
+
+            let raw_m = signal.value;
+
+            let continuous = match states.get(&key) {
+                Some(state) => !lli && state.phase == *phase && t - state.last <= max_gap,
+                None => false,
+            };
+
+            if !continuous {
+                if states.contains_key(&key) {
+                    summary.resets += 1;
+                }
+
+                states.insert(
+                    key,
+                    HatchState {
+                        last: t,
+                        phase: phase.clone(),
+                        phase_m,
+                        smoothed_m: raw_m,
+                        n: 1,
+                    },
+                );
+                continue;
+            }
+
+            let Some(state) = states.get_mut(&key) else {
+                continue;
+            };
+
+            state.n = (state.n + 1).min(opts.window);
+
+            let n = state.n as f64;
+            let smoothed_m =
+                raw_m / n + (n - 1.0) / n * (state.smoothed_m + phase_m - state.phase_m);
+
+            state.last = t;
+            state.phase_m = phase_m;
+            state.smoothed_m = smoothed_m;
+
+            summary.smoothed += 1;
+            summary.sum_squares += (raw_m - smoothed_m).powi(2);
+
+            signal.value = smoothed_m;
+        }
+    }
+
+    for summary in summaries.values() {
+        debug!(
+            "{}({}): {}/{} smoothed samples, {} reset(s), rms={:.3}m",
+            summary.sv,
+            summary.code,
+            summary.smoothed,
+            summary.samples,
+            summary.resets,
+            summary.rms_m()
+        );
+    }
+
+    summaries.into_values().collect()
+}
+
+/// Writes the code smoothing summary as CSV
+#[cfg(feature = "csv")]
+pub fn write_csv(summaries: &[SmoothedCode], path: &Path) -> Result<(), csv::Error> {
+    let mut w = Writer::from_path(path)?;
+    w.write_record(["SV", "Code", "Samples", "Smoothed", "Resets", "RMS [m]"])?;
+
+    for summary in summaries.iter() {
+        w.write_record(&[
+            summary.sv.to_string(),
+            summary.code.to_string(),
+            summary.samples.to_string(),
+            summary.smoothed.to_string(),
+            summary.resets.to_string(),
+            format!("{:.3}", summary.rms_m()),
+        ])?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
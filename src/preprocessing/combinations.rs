@@ -0,0 +1,320 @@
+//! Signal combinations, synthesized as virtual observables
+use std::{collections::HashMap, str::FromStr};
+
+use rinex::prelude::{Carrier, Observable, Rinex, SV};
+
+const SPEED_OF_LIGHT_M_S: f64 = 299_792_458.0;
+
+/// Supported combinations
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    /// Ionosphere free
+    IonosphereFree,
+    /// Geometry free
+    GeometryFree,
+    /// Wide lane
+    WideLane,
+    /// Narrow lane
+    NarrowLane,
+    /// Melbourne-Wübbena: phase wide lane minus code narrow lane
+    MelbourneWubbena,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "if" => Ok(Self::IonosphereFree),
+            "gf" => Ok(Self::GeometryFree),
+            "wl" => Ok(Self::WideLane),
+            "nl" => Ok(Self::NarrowLane),
+            "mw" => Ok(Self::MelbourneWubbena),
+            _ => Err(format!(
+                "unknown combination \"{}\", expecting if, gf, wl, nl or mw",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::IonosphereFree => write!(f, "IF"),
+            Self::GeometryFree => write!(f, "GF"),
+            Self::WideLane => write!(f, "WL"),
+            Self::NarrowLane => write!(f, "NL"),
+            Self::MelbourneWubbena => write!(f, "MW"),
+        }
+    }
+}
+
+/// Combination of two signals, described like "comb:if:C1C,C5Q".
+/// The Melbourne-Wübbena combination uses both the pseudo ranges and the phases
+/// of the two signals, whichever are described ("comb:mw:C1C,C5Q" or "comb:mw:L1C,L5Q").
+#[derive(Debug, Clone, PartialEq)]
+pub struct Combination {
+    kind: Kind,
+    /// Combined signals
+    signals: (Observable, Observable),
+    /// Pseudo ranges and phases, in [Kind::MelbourneWubbena]
+    codes: (Observable, Observable),
+    phases: (Observable, Observable),
+    /// Name of the synthesized observable
+    name: Observable,
+}
+
+/// Returns the observable of same carrier and tracking code, of another kind
+/// ('C' for pseudo range, 'L' for phase).
+fn twin(observable: &Observable, kind: char) -> Result<Observable, String> {
+    let code = observable.to_string();
+    let attributes = code.get(1..).unwrap_or_default();
+    let twin = format!("{}{}", kind, attributes);
+    Observable::from_str(&twin).map_err(|_| format!("invalid observable \"{}\"", twin))
+}
+
+impl Combination {
+    /// True if this `-P` filter description describes a [Combination]
+    pub fn is_combination(desc: &str) -> bool {
+        desc.trim().to_lowercase().starts_with("comb:")
+    }
+
+    /// Synthesized [Observable]
+    fn observable(&self) -> &Observable {
+        &self.name
+    }
+}
+
+/// True if this [Observable] was synthesized by a [Combination].
+/// Combinations are always expressed in meters, phase combinations included,
+/// unlike RINEX phase observations (in cycles). Their name ("IF:C1C+C5Q")
+/// is not a valid RINEX code, which is what distinguishes them.
+pub fn is_synthesized(observable: &Observable) -> bool {
+    match observable {
+        Observable::Phase(name) | Observable::PseudoRange(name) => name.contains(':'),
+        _ => false,
+    }
+}
+
+/// Returns a copy of this [Rinex] without the synthesized observables,
+/// which cannot be formatted in RINEX, or None if it does not contain any.
+pub fn without_synthesized(rinex: &Rinex) -> Option<Rinex> {
+    let declared = rinex.header.obs.as_ref().map_or(false, |obs| {
+        obs.codes.values().flatten().any(is_synthesized)
+    });
+
+    if !declared {
+        return None;
+    }
+
+    let mut rinex = rinex.clone();
+
+    if let Some(obs) = &mut rinex.header.obs {
+        for codes in obs.codes.values_mut() {
+            codes.retain(|code| !is_synthesized(code));
+        }
+    }
+
+    if let Some(record) = rinex.record.as_mut_obs() {
+        for (_, v) in record.iter_mut() {
+            v.signals
+                .retain(|signal| !is_synthesized(&signal.observable));
+        }
+    }
+
+    Some(rinex)
+}
+
+impl FromStr for Combination {
+    type Err = String;
+    fn from_str(desc: &str) -> Result<Self, Self::Err> {
+        let mut items = desc.split(':').map(|item| item.trim());
+
+        if items.next().map(|item| item.to_lowercase()) != Some("comb".to_string()) {
+            return Err("expecting \"comb:kind:signal1,signal2\"".to_string());
+        }
+
+        let kind = Kind::from_str(items.next().unwrap_or_default())?;
+
+        let signals = items
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(|item| {
+                Observable::from_str(item.trim())
+                    .map_err(|_| format!("invalid observable \"{}\"", item.trim()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (lhs, rhs) = match signals.as_slice() {
+            [lhs, rhs] if lhs != rhs => (lhs.clone(), rhs.clone()),
+            _ => return Err("expecting two different signals".to_string()),
+        };
+
+        let is_code = |obs: &Observable| obs.is_pseudorange_observable();
+        let is_phase = |obs: &Observable| obs.is_phase_observable();
+
+        let consistent = (is_code(&lhs) && is_code(&rhs)) || (is_phase(&lhs) && is_phase(&rhs));
+
+        if !consistent {
+            return Err("expecting two pseudo ranges or two phases".to_string());
+        }
+
+        let codes = (twin(&lhs, 'C')?, twin(&rhs, 'C')?);
+        let phases = (twin(&lhs, 'L')?, twin(&rhs, 'L')?);
+
+        let name = format!("{}:{}+{}", kind, lhs, rhs);
+
+        // the combination is expressed in meters, of the same kind as its inputs
+        let name = if kind == Kind::MelbourneWubbena || is_phase(&lhs) {
+            Observable::Phase(name)
+        } else {
+            Observable::PseudoRange(name)
+        };
+
+        Ok(Self {
+            kind,
+            signals: (lhs, rhs),
+            codes,
+            phases,
+            name,
+        })
+    }
+}
+
+/// One SV observation, in meters, with its frequency
+struct Sample {
+    frequency: f64,
+    value_m: f64,
+}
+
+/// Returns the samples of this SV, in meters, indexed by [Observable]
+fn samples(sv: SV, signals: &[(Observable, f64)]) -> HashMap<Observable, Sample> {
+    signals
+        .iter()
+        .filter_map(|(observable, value)| {
+            let frequency = Carrier::from_observable(sv.constellation, observable)
+                .ok()?
+                .frequency();
+
+            let value_m = if observable.is_phase_observable() {
+                value * SPEED_OF_LIGHT_M_S / frequency
+            } else {
+                *value
+            };
+
+            Some((observable.clone(), Sample { frequency, value_m }))
+        })
+        .collect()
+}
+
+/// Evaluates this combination, returns None if one signal is missing
+fn evaluate(comb: &Combination, samples: &HashMap<Observable, Sample>) -> Option<f64> {
+    let wide_lane = |a: &Sample, b: &Sample| {
+        (a.frequency * a.value_m - b.frequency * b.value_m) / (a.frequency - b.frequency)
+    };
+
+    let narrow_lane = |a: &Sample, b: &Sample| {
+        (a.frequency * a.value_m + b.frequency * b.value_m) / (a.frequency + b.frequency)
+    };
+
+    if comb.kind == Kind::MelbourneWubbena {
+        let (l1, l2) = (samples.get(&comb.phases.0)?, samples.get(&comb.phases.1)?);
+        let (c1, c2) = (samples.get(&comb.codes.0)?, samples.get(&comb.codes.1)?);
+
+        if l1.frequency == l2.frequency {
+            return None;
+        }
+
+        return Some(wide_lane(l1, l2) - narrow_lane(c1, c2));
+    }
+
+    let (a, b) = (samples.get(&comb.signals.0)?, samples.get(&comb.signals.1)?);
+
+    if a.frequency == b.frequency && comb.kind != Kind::GeometryFree {
+        return None;
+    }
+
+    Some(match comb.kind {
+        Kind::IonosphereFree => {
+            let (f1_2, f2_2) = (a.frequency.powi(2), b.frequency.powi(2));
+            (f1_2 * a.value_m - f2_2 * b.value_m) / (f1_2 - f2_2)
+        },
+        Kind::GeometryFree => a.value_m - b.value_m,
+        Kind::WideLane => wide_lane(a, b),
+        Kind::NarrowLane => narrow_lane(a, b),
+        Kind::MelbourneWubbena => unreachable!("handled above"),
+    })
+}
+
+/// Synthesizes this [Combination] in this Observation [Rinex], as a new observable
+/// of each SV that observed the required signals. Returns the number of synthesized samples.
+pub fn process(rinex: &mut Rinex, comb: &Combination) -> usize {
+    let mut constellations = Vec::new();
+    let mut total = 0;
+
+    if let Some(record) = rinex.record.as_mut_obs() {
+        for (_, v) in record.iter_mut() {
+            let mut per_sv = HashMap::<SV, Vec<(Observable, f64)>>::new();
+
+            for signal in v.signals.iter() {
+                per_sv
+                    .entry(signal.sv)
+                    .or_default()
+                    .push((signal.observable.clone(), signal.value));
+            }
+
+            for (sv, signals) in per_sv.iter() {
+                let value = match evaluate(comb, &samples(*sv, signals)) {
+                    Some(value) => value,
+                    None => continue,
+                };
+
+                // flags of the combined signals
+                let template = v
+                    .signals
+                    .iter()
+                    .find(|signal| signal.sv == *sv && signal.observable == comb.signals.0);
+
+                let lli = v
+                    .signals
+                    .iter()
+                    .filter(|signal| signal.sv == *sv)
+                    .filter(|signal| {
+                        signal.observable == comb.signals.0 || signal.observable == comb.signals.1
+                    })
+                    .filter_map(|signal| signal.lli)
+                    .reduce(|a, b| a | b);
+
+                let mut signal = match template {
+                    Some(template) => template.clone(),
+                    None => continue,
+                };
+
+                signal.observable = comb.observable().clone();
+                signal.value = value;
+                signal.lli = lli;
+                signal.snr = None;
+
+                v.signals.push(signal);
+                total += 1;
+
+                if !constellations.contains(&sv.constellation) {
+                    constellations.push(sv.constellation);
+                }
+            }
+        }
+    }
+
+    // declare the new observable
+    if let Some(obs) = &mut rinex.header.obs {
+        for constellation in constellations {
+            let codes = obs.codes.entry(constellation).or_default();
+            if !codes.contains(comb.observable()) {
+                codes.push(comb.observable().clone());
+            }
+        }
+    }
+
+    total
+}
@@ -0,0 +1,586 @@
+//! Cycle slips detection and repair
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    str::FromStr,
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use gnss_qc::prelude::{html, Markup, QcExtraPage, Render};
+
+use rinex::prelude::{obs::LliFlags, Carrier, Duration, Epoch, Observable, Rinex, SV};
+
+#[cfg(feature = "csv")]
+use csv::Writer;
+
+#[cfg(feature = "csv")]
+use std::path::Path;
+
+const SPEED_OF_LIGHT_M_S: f64 = 299_792_458.0;
+
+/// A data gap larger than this many sampling periods starts a new arc
+const MAX_GAP_PERIODS: f64 = 10.0;
+
+/// What to do with detected cycle slips
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CycleSlipMode {
+    /// Flag the slipped phase observations (Loss of Lock indicator)
+    Flag,
+    /// Flag and split phase arcs at every slip, discarding short arcs
+    Split,
+    /// Repair the slipped phase observations, when the jump can be estimated
+    Repair,
+}
+
+impl FromStr for CycleSlipMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "flag" => Ok(Self::Flag),
+            "split" => Ok(Self::Split),
+            "repair" => Ok(Self::Repair),
+            _ => Err(format!("unknown cycle slip mode \"{}\"", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for CycleSlipMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Flag => write!(f, "flag"),
+            Self::Split => write!(f, "split"),
+            Self::Repair => write!(f, "repair"),
+        }
+    }
+}
+
+/// Cycle slips detection settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CycleSlipOptions {
+    /// Selected [CycleSlipMode]
+    pub mode: CycleSlipMode,
+    /// Geometry free combination threshold, in meters
+    pub gf_threshold_m: f64,
+    /// Melbourne-Wübbena combination threshold, in (wide lane) cycles
+    pub mw_threshold_cycles: f64,
+    /// Minimal arc length (in epochs), in [CycleSlipMode::Split] mode
+    pub min_arc_epochs: usize,
+}
+
+/// Cycle slip detection method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Detection {
+    /// Loss of Lock indicator, as reported by the receiver
+    Lli,
+    /// Geometry free combination
+    GeometryFree,
+    /// Melbourne-Wübbena combination
+    MelbourneWubbena,
+}
+
+impl std::fmt::Display for Detection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Lli => write!(f, "LLI"),
+            Self::GeometryFree => write!(f, "GF"),
+            Self::MelbourneWubbena => write!(f, "MW"),
+        }
+    }
+}
+
+/// One detected cycle slip
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleSlip {
+    /// [Epoch] of the first slipped observation
+    pub epoch: Epoch,
+    /// Satellite
+    pub sv: SV,
+    /// Phase signal
+    pub signal: Observable,
+    /// Detection methods
+    pub detections: Vec<Detection>,
+    /// Estimated jump, in cycles, when it could be determined
+    pub jump_cycles: Option<f64>,
+    /// True if this slip was repaired
+    pub repaired: bool,
+}
+
+/// Combinations of one SV and pair of phase signals,
+/// during one continuous arc
+struct ArcState {
+    last: Epoch,
+    gf: f64,
+    mw_mean: Option<f64>,
+    mw_count: usize,
+}
+
+/// One phase sample of one SV
+struct Phase {
+    signal: Observable,
+    frequency: f64,
+    cycles: f64,
+    code_m: Option<f64>,
+    lli: bool,
+}
+
+/// Collects all phase samples of one SV at one epoch, sorted by decreasing frequency.
+fn phase_samples(sv: SV, signals: &[(&Observable, f64, Option<LliFlags>)]) -> Vec<Phase> {
+    let mut codes = HashMap::<Carrier, f64>::new();
+
+    for (observable, value, _) in signals.iter() {
+        if observable.is_pseudorange_observable() {
+            if let Ok(carrier) = Carrier::from_observable(sv.constellation, observable) {
+                codes.insert(carrier, *value);
+            }
+        }
+    }
+
+    let mut phases = signals
+        .iter()
+        .filter(|(observable, _, _)| observable.is_phase_observable())
+        .filter_map(|(observable, value, lli)| {
+            let carrier = Carrier::from_observable(sv.constellation, observable).ok()?;
+            Some(Phase {
+                signal: (*observable).clone(),
+                frequency: carrier.frequency(),
+                cycles: *value,
+                code_m: codes.get(&carrier).copied(),
+                lli: lli.map_or(false, |lli| lli.intersects(LliFlags::LOCK_LOSS)),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    phases.sort_by(|a, b| b.frequency.total_cmp(&a.frequency));
+    phases
+}
+
+/// Detects all cycle slips in this Observation [Rinex]
+pub fn detect(rinex: &Rinex, opts: &CycleSlipOptions) -> Vec<CycleSlip> {
+    let max_gap = rinex
+        .dominant_sampling_interval()
+        .map(|dt| dt * MAX_GAP_PERIODS)
+        .unwrap_or(Duration::from_hours(1.0));
+
+    let mut arcs = HashMap::<(SV, Observable, Observable), ArcState>::new();
+    let mut slips = BTreeMap::<(Epoch, SV, Observable), CycleSlip>::new();
+
+    let mut add_slip =
+        |epoch: Epoch, sv: SV, signal: &Observable, detection: Detection, jump: Option<f64>| {
+            let slip = slips
+                .entry((epoch, sv, signal.clone()))
+                .or_insert_with(|| CycleSlip {
+                    epoch,
+                    sv,
+                    signal: signal.clone(),
+                    detections: Vec::new(),
+                    jump_cycles: None,
+                    repaired: false,
+                });
+
+            if !slip.detections.contains(&detection) {
+                slip.detections.push(detection);
+            }
+
+            if jump.is_some() {
+                slip.jump_cycles = jump;
+            }
+        };
+
+    for (k, v) in rinex.observations_iter() {
+        if !k.flag.is_ok() {
+            continue;
+        }
+
+        let t = k.epoch;
+
+        let mut per_sv = BTreeMap::<SV, Vec<(&Observable, f64, Option<LliFlags>)>>::new();
+
+        for signal in v.signals.iter() {
+            per_sv.entry(signal.sv).or_default().push((
+                &signal.observable,
+                signal.value,
+                signal.lli,
+            ));
+        }
+
+        for (sv, signals) in per_sv.iter() {
+            let phases = phase_samples(*sv, signals);
+
+            for phase in phases.iter().filter(|phase| phase.lli) {
+                add_slip(t, *sv, &phase.signal, Detection::Lli, None);
+            }
+
+            // dual frequency combinations
+            let (p1, p2) = match phases
+                .iter()
+                .tuple_windows()
+                .find(|(a, b)| a.frequency != b.frequency)
+            {
+                Some(pair) => pair,
+                None => continue,
+            };
+
+            let (f1, f2) = (p1.frequency, p2.frequency);
+            let (l1, l2) = (SPEED_OF_LIGHT_M_S / f1, SPEED_OF_LIGHT_M_S / f2);
+
+            let gf = l1 * p1.cycles - l2 * p2.cycles;
+
+            let mw = match (p1.code_m, p2.code_m) {
+                (Some(c1), Some(c2)) => {
+                    let lw = SPEED_OF_LIGHT_M_S / (f1 - f2);
+                    let phase_wl = (f1 * l1 * p1.cycles - f2 * l2 * p2.cycles) / (f1 - f2);
+                    let code_nl = (f1 * c1 + f2 * c2) / (f1 + f2);
+                    Some((phase_wl - code_nl) / lw)
+                },
+                _ => None,
+            };
+
+            let key = (*sv, p1.signal.clone(), p2.signal.clone());
+
+            let continuous = !p1.lli
+                && !p2.lli
+                && arcs
+                    .get(&key)
+                    .map_or(false, |state| t - state.last <= max_gap);
+
+            if !continuous {
+                // new arc
+                arcs.insert(
+                    key,
+                    ArcState {
+                        last: t,
+                        gf,
+                        mw_mean: mw,
+                        mw_count: mw.map_or(0, |_| 1),
+                    },
+                );
+                continue;
+            }
+
+            let Some(state) = arcs.get_mut(&key) else {
+                continue;
+            };
+
+            let dgf = gf - state.gf;
+            let gf_slip = dgf.abs() > opts.gf_threshold_m;
+
+            let dmw = match (mw, state.mw_mean) {
+                (Some(mw), Some(mean)) => Some(mw - mean),
+                _ => None,
+            };
+
+            let mw_slip = dmw.map_or(false, |dmw| dmw.abs() > opts.mw_threshold_cycles);
+
+            if gf_slip || mw_slip {
+                // jumps can only be estimated when both combinations exist:
+                // dN1 - dN2 = dMW and l1 dN1 - l2 dN2 = dGF
+                let (jump1, jump2) = match dmw {
+                    Some(dmw) => {
+                        let nw = dmw.round();
+                        let n1 = ((dgf - l2 * nw) / (l1 - l2)).round();
+                        (Some(n1), Some(n1 - nw))
+                    },
+                    None => (None, None),
+                };
+
+                for (phase, jump) in [(p1, jump1), (p2, jump2)] {
+                    if gf_slip {
+                        add_slip(t, *sv, &phase.signal, Detection::GeometryFree, jump);
+                    }
+                    if mw_slip {
+                        add_slip(t, *sv, &phase.signal, Detection::MelbourneWubbena, jump);
+                    }
+                }
+
+                // new arc
+                state.mw_mean = mw;
+                state.mw_count = mw.map_or(0, |_| 1);
+            } else if let Some(mw) = mw {
+                // running average
+                state.mw_count += 1;
+                let mean = state.mw_mean.unwrap_or(mw);
+                state.mw_mean = Some(mean + (mw - mean) / state.mw_count as f64);
+            }
+
+            state.gf = gf;
+            state.last = t;
+        }
+    }
+
+    slips.into_values().collect()
+}
+
+/// Detects all cycle slips in this Observation [Rinex], then
+/// flags, splits or repairs them. Returns the detected cycle slips.
+pub fn process(rinex: &mut Rinex, opts: &CycleSlipOptions) -> Vec<CycleSlip> {
+    let mut slips = detect(rinex, opts);
+
+    let mut repairs = HashMap::<(SV, Observable), Vec<(Epoch, f64)>>::new();
+    let mut flags = HashSet::<(Epoch, SV, Observable)>::new();
+
+    for slip in slips.iter_mut() {
+        match (opts.mode, slip.jump_cycles) {
+            (CycleSlipMode::Repair, Some(jump)) => {
+                repairs
+                    .entry((slip.sv, slip.signal.clone()))
+                    .or_default()
+                    .push((slip.epoch, jump));
+                slip.repaired = true;
+            },
+            _ => {
+                flags.insert((slip.epoch, slip.sv, slip.signal.clone()));
+            },
+        }
+    }
+
+    let discarded = match opts.mode {
+        CycleSlipMode::Split => short_arcs(rinex, &flags, opts.min_arc_epochs),
+        _ => HashSet::new(),
+    };
+
+    let record = match rinex.record.as_mut_obs() {
+        Some(record) => record,
+        None => return slips,
+    };
+
+    for (k, v) in record.iter_mut() {
+        for signal in v.signals.iter_mut() {
+            let key = (k.epoch, signal.sv, signal.observable.clone());
+
+            if flags.contains(&key) {
+                let lli = signal.lli.unwrap_or(LliFlags::OK_OR_UNKNOWN);
+                signal.lli = Some(lli | LliFlags::LOCK_LOSS);
+            }
+
+            if let Some(jumps) = repairs.get(&(signal.sv, signal.observable.clone())) {
+                let correction = jumps
+                    .iter()
+                    .filter(|(t, _)| *t <= k.epoch)
+                    .map(|(_, jump)| jump)
+                    .sum::<f64>();
+
+                signal.value -= correction;
+            }
+        }
+
+        if !discarded.is_empty() {
+            v.signals.retain(|signal| {
+                !discarded.contains(&(k.epoch, signal.sv, signal.observable.clone()))
+            });
+        }
+    }
+
+    slips
+}
+
+/// Returns the phase samples that belong to arcs shorter than `min_epochs`.
+/// Arcs are split at every slip and every data gap.
+fn short_arcs(
+    rinex: &Rinex,
+    slips: &HashSet<(Epoch, SV, Observable)>,
+    min_epochs: usize,
+) -> HashSet<(Epoch, SV, Observable)> {
+    let max_gap = rinex
+        .dominant_sampling_interval()
+        .map(|dt| dt * MAX_GAP_PERIODS)
+        .unwrap_or(Duration::from_hours(1.0));
+
+    let mut arcs = HashMap::<(SV, Observable), Vec<Vec<Epoch>>>::new();
+
+    for (k, v) in rinex.observations_iter() {
+        for signal in v.signals.iter() {
+            if !signal.observable.is_phase_observable() {
+                continue;
+            }
+
+            let key = (signal.sv, signal.observable.clone());
+            let arcs = arcs.entry(key).or_default();
+
+            let new_arc = match arcs.last().and_then(|arc| arc.last()) {
+                Some(last) => {
+                    k.epoch - *last > max_gap
+                        || slips.contains(&(k.epoch, signal.sv, signal.observable.clone()))
+                },
+                None => true,
+            };
+
+            if new_arc {
+                arcs.push(vec![k.epoch]);
+            } else if let Some(arc) = arcs.last_mut() {
+                arc.push(k.epoch);
+            }
+        }
+    }
+
+    let mut discarded = HashSet::new();
+
+    for ((sv, observable), arcs) in arcs.iter() {
+        for arc in arcs.iter().filter(|arc| arc.len() < min_epochs) {
+            for t in arc.iter() {
+                discarded.insert((*t, *sv, observable.clone()));
+            }
+        }
+    }
+
+    if !discarded.is_empty() {
+        debug!("{} phase samples discarded (short arcs)", discarded.len());
+    }
+
+    discarded
+}
+
+/// Writes the list of cycle slips as CSV
+#[cfg(feature = "csv")]
+pub fn write_csv(slips: &[CycleSlip], path: &Path) -> Result<(), csv::Error> {
+    let mut w = Writer::from_path(path)?;
+    w.write_record([
+        "Epoch",
+        "SV",
+        "Signal",
+        "Detection",
+        "Jump [cycles]",
+        "Repaired",
+    ])?;
+
+    for slip in slips.iter() {
+        w.write_record(&[
+            slip.epoch.to_string(),
+            slip.sv.to_string(),
+            slip.signal.to_string(),
+            slip.detections.iter().join("+"),
+            slip.jump_cycles
+                .map(|jump| format!("{:.0}", jump))
+                .unwrap_or_else(|| "None".to_string()),
+            slip.repaired.to_string(),
+        ])?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
+
+struct ReportTab {}
+
+impl Render for ReportTab {
+    fn render(&self) -> Markup {
+        html! {
+            a id="menu:cycle-slips" {
+                span class="icon" {
+                    i class="fa-solid fa-scissors" {}
+                }
+                "Cycle slips"
+            }
+        }
+    }
+}
+
+/// Cycle slips of one SV and signal
+struct SignalSlips {
+    sv: SV,
+    signal: Observable,
+    count: usize,
+    repaired: usize,
+    detections: String,
+    epochs: String,
+}
+
+/// Cycle slips report
+struct ReportContent {
+    mode: CycleSlipMode,
+    signals: Vec<SignalSlips>,
+}
+
+impl ReportContent {
+    fn new(mode: CycleSlipMode, slips: &[CycleSlip]) -> Self {
+        let mut signals = Vec::<SignalSlips>::new();
+
+        for ((sv, signal), slips) in slips
+            .iter()
+            .sorted_by(|a, b| (a.sv, &a.signal).cmp(&(b.sv, &b.signal)))
+            .chunk_by(|slip| (slip.sv, slip.signal.clone()))
+            .into_iter()
+        {
+            let slips = slips.collect::<Vec<_>>();
+
+            signals.push(SignalSlips {
+                sv,
+                signal,
+                count: slips.len(),
+                repaired: slips.iter().filter(|slip| slip.repaired).count(),
+                detections: slips
+                    .iter()
+                    .flat_map(|slip| slip.detections.iter())
+                    .unique()
+                    .sorted()
+                    .join(", "),
+                epochs: slips.iter().map(|slip| slip.epoch).join(", "),
+            });
+        }
+
+        Self { mode, signals }
+    }
+}
+
+impl Render for ReportContent {
+    fn render(&self) -> Markup {
+        html! {
+            div class="table-container" {
+                table class="table is-bordered" {
+                    tbody {
+                        tr {
+                            th class="is-info" {
+                                "Mode"
+                            }
+                            td {
+                                (self.mode.to_string())
+                            }
+                        }
+                        tr {
+                            th class="is-info" {
+                                "Cycle slips"
+                            }
+                            td {
+                                (self.signals.iter().map(|s| s.count).sum::<usize>())
+                            }
+                        }
+                    }
+                }
+                table class="table is-bordered" {
+                    thead {
+                        tr {
+                            th { "SV" }
+                            th { "Signal" }
+                            th { "Slips" }
+                            th { "Repaired" }
+                            th { "Detection" }
+                            th { "Epochs" }
+                        }
+                    }
+                    tbody {
+                        @for signal in self.signals.iter() {
+                            tr {
+                                td { (signal.sv.to_string()) }
+                                td { (signal.signal.to_string()) }
+                                td { (signal.count) }
+                                td { (signal.repaired) }
+                                td { (signal.detections) }
+                                td { (signal.epochs) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Creates the cycle slips report page
+pub fn extra_page(mode: CycleSlipMode, slips: &[CycleSlip]) -> QcExtraPage {
+    QcExtraPage {
+        tab: Box::new(ReportTab {}),
+        html_id: "cycle-slips".to_string(),
+        content: Box::new(ReportContent::new(mode, slips)),
+    }
+}
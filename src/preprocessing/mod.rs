@@ -0,0 +1,728 @@
+//! Preprocessing pipeline
+use std::{fs::read_to_string, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use anise::prelude::Orbit;
+
+use crate::{loader::rinex_product_type, Cli};
+
+use gnss_qc::prelude::{
+    Filter as QcFilter, FilterItem as QcFilterItem, MaskOperand as QcMaskOperand, Preprocessing,
+    ProductType, QcContext, Repair, RepairTrait, SP3,
+};
+
+use rinex::prelude::{Constellation, Rinex, SV};
+
+#[cfg(feature = "ppp")]
+use gnss_qc::prelude::NavFilter;
+
+mod clock_jumps;
+mod code_smoothing;
+mod combinations;
+mod cycle_slips;
+mod outliers;
+mod report;
+mod resampling;
+mod signal_mask;
+mod sky_mask;
+
+pub use clock_jumps::ClockJumpMode;
+pub use code_smoothing::CodeSmoothingOptions;
+pub use combinations::{is_synthesized, without_synthesized};
+pub use cycle_slips::{CycleSlipMode, CycleSlipOptions};
+pub use outliers::{MaskedSamples, OutlierAction, OutlierMethod, OutlierOptions};
+pub use report::PreprocessingReport;
+pub use resampling::{Interpolation, ResamplingOptions};
+pub use sky_mask::SkyMask;
+
+use combinations::Combination;
+use signal_mask::SignalMask;
+
+/// Errors that may happen while designing the preprocessing pipeline.
+/// They are all reported before any input file is loaded.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid filter \"{0}\": {1}")]
+    InvalidFilter(String, String),
+    #[cfg(feature = "ppp")]
+    #[error("invalid NAV filter \"{0}\": {1}")]
+    InvalidNavFilter(String, String),
+    #[cfg(not(feature = "ppp"))]
+    #[error("NAV filters require the \"ppp\" feature")]
+    NavFilterNotAvailable,
+    #[error("invalid cycle slips options: {0}")]
+    InvalidCycleSlipOptions(String),
+    #[error("invalid outliers options: {0}")]
+    InvalidOutlierOptions(String),
+    #[error("invalid code smoothing options: {0}")]
+    InvalidCodeSmoothingOptions(String),
+    #[error("invalid resampling options: {0}")]
+    InvalidResamplingOptions(String),
+    #[error("invalid sky mask: {0}")]
+    InvalidSkyMask(String),
+    #[error("failed to read terrain mask \"{0}\": {1}")]
+    TerrainMaskRead(String, std::io::Error),
+    #[error("invalid terrain mask \"{0}\": {1}")]
+    TerrainMaskParsing(String, String),
+    #[error("failed to read pipeline \"{0}\": {1}")]
+    PipelineRead(String, std::io::Error),
+    #[error("invalid pipeline \"{0}\": {1}")]
+    PipelineParsing(String, serde_json::Error),
+}
+
+/// Constellations that may be masked out, with a dedicated command line flag
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConstellationMask {
+    #[serde(rename = "gps")]
+    Gps,
+    #[serde(rename = "glonass")]
+    Glonass,
+    #[serde(rename = "galileo")]
+    Galileo,
+    #[serde(rename = "beidou")]
+    BeiDou,
+    /// BeiDou GEO vehicles only
+    #[serde(rename = "beidou-geo")]
+    BeiDouGeo,
+    #[serde(rename = "sbas")]
+    Sbas,
+    #[serde(rename = "qzss")]
+    Qzss,
+    #[serde(rename = "irnss")]
+    Irnss,
+}
+
+impl std::fmt::Display for ConstellationMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Gps => write!(f, "GPS"),
+            Self::Glonass => write!(f, "Glonass"),
+            Self::Galileo => write!(f, "Galileo"),
+            Self::BeiDou => write!(f, "BeiDou"),
+            Self::BeiDouGeo => write!(f, "BeiDou GEO"),
+            Self::Sbas => write!(f, "SBAS"),
+            Self::Qzss => write!(f, "QZSS"),
+            Self::Irnss => write!(f, "IRNSS"),
+        }
+    }
+}
+
+impl ConstellationMask {
+    /// Returns the [QcFilter]s that mask out these vehicles
+    fn filters(&self) -> Vec<QcFilter> {
+        let constellation = match self {
+            Self::Gps => Constellation::GPS,
+            Self::Glonass => Constellation::Glonass,
+            Self::Galileo => Constellation::Galileo,
+            Self::BeiDou => Constellation::BeiDou,
+            Self::Sbas => Constellation::SBAS,
+            Self::Qzss => Constellation::QZSS,
+            Self::Irnss => Constellation::IRNSS,
+            Self::BeiDouGeo => {
+                // GEO vehicles are C01-C05 and C59+
+                return vec![
+                    QcFilter::mask(
+                        QcMaskOperand::GreaterThan,
+                        QcFilterItem::SvItem(vec![SV::new(Constellation::BeiDou, 5)]),
+                    ),
+                    QcFilter::mask(
+                        QcMaskOperand::LowerThan,
+                        QcFilterItem::SvItem(vec![SV::new(Constellation::BeiDou, 59)]),
+                    ),
+                ];
+            },
+        };
+
+        vec![QcFilter::mask(
+            QcMaskOperand::NotEquals,
+            QcFilterItem::ConstellationItem(vec![constellation]),
+        )]
+    }
+}
+
+/// Product a filter may be restricted to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "obs")]
+    Observation,
+    #[serde(rename = "met")]
+    Meteo,
+    #[serde(rename = "nav")]
+    Navigation,
+    #[serde(rename = "clk")]
+    Clock,
+    #[serde(rename = "sp3")]
+    Sp3,
+    #[serde(rename = "ionex")]
+    Ionex,
+    #[serde(rename = "doris")]
+    Doris,
+}
+
+impl Scope {
+    /// All scopes, with their filter description prefix
+    const PREFIXES: [(&'static str, Self); 7] = [
+        ("obs", Self::Observation),
+        ("met", Self::Meteo),
+        ("nav", Self::Navigation),
+        ("clk", Self::Clock),
+        ("sp3", Self::Sp3),
+        ("ionex", Self::Ionex),
+        ("doris", Self::Doris),
+    ];
+
+    /// Splits a filter description into its [Scope] (if any)
+    /// and the actual filter description. For example "obs:decim:300s" applies
+    /// "decim:300s" to Observations only.
+    fn split(desc: &str) -> (Option<Self>, &str) {
+        if let Some((prefix, filter)) = desc.split_once(':') {
+            let prefix = prefix.trim().to_lowercase();
+            for (scope_prefix, scope) in Self::PREFIXES {
+                if prefix == scope_prefix {
+                    return (Some(scope), filter.trim());
+                }
+            }
+        }
+        (None, desc.trim())
+    }
+
+    /// Returns the [ProductType] this [Scope] applies to
+    fn product_type(&self) -> ProductType {
+        match self {
+            Self::Observation => ProductType::Observation,
+            Self::Meteo => ProductType::MeteoObservation,
+            Self::Navigation => ProductType::BroadcastNavigation,
+            Self::Clock => ProductType::HighPrecisionClock,
+            Self::Sp3 => ProductType::HighPrecisionOrbit,
+            Self::Ionex => ProductType::IONEX,
+            Self::Doris => ProductType::DORIS,
+        }
+    }
+}
+
+/// One preprocessing step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Step {
+    /// Masks out one constellation
+    ConstellationMask(ConstellationMask),
+    /// [QcFilter], possibly restricted to one product
+    Filter {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scope: Option<Scope>,
+        description: String,
+    },
+    /// Elevation, azimuth or terrain mask, applied to Observations
+    SkyMask(SkyMask),
+    /// SNR or C/N0 mask, applied to Observations
+    SignalMask(String),
+    /// Navigation filter (--nav)
+    NavFilter(String),
+    /// Zero values repair
+    ZeroRepair,
+    /// Receiver clock jumps detection, and possible repair
+    ClockJumps(ClockJumpMode),
+    /// Outliers rejection
+    Outliers(OutlierOptions),
+    /// Cycle slips detection, and possible repair
+    CycleSlips(CycleSlipOptions),
+    /// Carrier smoothed pseudo ranges (Hatch filter)
+    CodeSmoothing(CodeSmoothingOptions),
+    /// Signal combination, synthesized as a new observable
+    Combination(String),
+    /// Resampling onto a regular time grid
+    Resampling(ResamplingOptions),
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ConstellationMask(mask) => write!(f, "{} filtered out", mask),
+            Self::Filter {
+                scope: Some(scope),
+                description,
+            } => write!(f, "{:?} filter \"{}\"", scope, description),
+            Self::Filter { description, .. } => write!(f, "filter \"{}\"", description),
+            Self::SkyMask(mask) => write!(f, "{}", mask),
+            Self::SignalMask(description) => write!(f, "signal mask \"{}\"", description),
+            Self::NavFilter(description) => write!(f, "NAV filter \"{}\"", description),
+            Self::ZeroRepair => write!(f, "zero repair"),
+            Self::ClockJumps(mode) => write!(f, "clock jumps ({})", mode),
+            Self::Outliers(opts) => write!(f, "{} outliers rejection", opts.method),
+            Self::CycleSlips(opts) => write!(f, "cycle slips ({})", opts.mode),
+            Self::CodeSmoothing(opts) => write!(f, "code smoothing ({} epochs)", opts.window),
+            Self::Combination(description) => write!(f, "combination \"{}\"", description),
+            Self::Resampling(opts) => {
+                write!(f, "{} resampling ({}s)", opts.method, opts.period_s)
+            },
+        }
+    }
+}
+
+/// [Step] ready to be applied
+enum Op {
+    Filters(Option<Scope>, Vec<QcFilter>),
+    SkyMask(SkyMask),
+    SignalMask(SignalMask),
+    #[cfg(feature = "ppp")]
+    NavFilter(NavFilter),
+    ZeroRepair,
+    ClockJumps(ClockJumpMode),
+    Outliers(OutlierOptions),
+    CycleSlips(CycleSlipOptions),
+    CodeSmoothing(CodeSmoothingOptions),
+    Combination(Combination),
+    Resampling(ResamplingOptions),
+}
+
+impl Step {
+    /// Validates this [Step] and converts it to an [Op]
+    fn op(&self) -> Result<Op, Error> {
+        match self {
+            Self::ConstellationMask(mask) => Ok(Op::Filters(None, mask.filters())),
+            Self::Filter { scope, description } => {
+                let filter = QcFilter::from_str(description)
+                    .map_err(|e| Error::InvalidFilter(description.clone(), e.to_string()))?;
+                Ok(Op::Filters(*scope, vec![filter]))
+            },
+            Self::SkyMask(mask) => {
+                mask.validate().map_err(Error::InvalidSkyMask)?;
+                Ok(Op::SkyMask(mask.clone()))
+            },
+            Self::SignalMask(description) => {
+                let mask = SignalMask::from_str(description)
+                    .map_err(|e| Error::InvalidFilter(description.clone(), e))?;
+                Ok(Op::SignalMask(mask))
+            },
+            #[cfg(feature = "ppp")]
+            Self::NavFilter(description) => {
+                let filter = NavFilter::from_str(description)
+                    .map_err(|e| Error::InvalidNavFilter(description.clone(), e.to_string()))?;
+                Ok(Op::NavFilter(filter))
+            },
+            #[cfg(not(feature = "ppp"))]
+            Self::NavFilter(_) => Err(Error::NavFilterNotAvailable),
+            Self::ZeroRepair => Ok(Op::ZeroRepair),
+            Self::ClockJumps(mode) => Ok(Op::ClockJumps(*mode)),
+            Self::Outliers(opts) => {
+                if opts.threshold <= 0.0 {
+                    return Err(Error::InvalidOutlierOptions(
+                        "threshold should be positive".to_string(),
+                    ));
+                }
+                if opts.half_window == 0 {
+                    return Err(Error::InvalidOutlierOptions(
+                        "window should be at least one sample".to_string(),
+                    ));
+                }
+                Ok(Op::Outliers(opts.clone()))
+            },
+            Self::CycleSlips(opts) => {
+                if opts.gf_threshold_m <= 0.0 || opts.mw_threshold_cycles <= 0.0 {
+                    return Err(Error::InvalidCycleSlipOptions(
+                        "thresholds should be positive".to_string(),
+                    ));
+                }
+                Ok(Op::CycleSlips(opts.clone()))
+            },
+            Self::CodeSmoothing(opts) => {
+                if opts.window == 0 {
+                    return Err(Error::InvalidCodeSmoothingOptions(
+                        "window should be at least one epoch".to_string(),
+                    ));
+                }
+                Ok(Op::CodeSmoothing(opts.clone()))
+            },
+            Self::Combination(description) => {
+                let comb = Combination::from_str(description)
+                    .map_err(|e| Error::InvalidFilter(description.clone(), e))?;
+                Ok(Op::Combination(comb))
+            },
+            Self::Resampling(opts) => {
+                if opts.period_s <= 0.0 || opts.max_gap_s <= 0.0 {
+                    return Err(Error::InvalidResamplingOptions(
+                        "period and maximal gap should be positive".to_string(),
+                    ));
+                }
+                if opts.method == Interpolation::Lagrange && opts.order == 0 {
+                    return Err(Error::InvalidResamplingOptions(
+                        "interpolation order should be at least 1".to_string(),
+                    ));
+                }
+                Ok(Op::Resampling(opts.clone()))
+            },
+        }
+    }
+}
+
+/// Ordered preprocessing steps, designed once from the command line,
+/// then applied identically to a [QcContext], a standalone [Rinex] or [SP3].
+/// The pipeline serializes as a list of steps, that can be replayed with --pipeline.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PreprocessingPipeline {
+    steps: Vec<Step>,
+}
+
+impl PreprocessingPipeline {
+    /// Designs the [PreprocessingPipeline] described by [Cli].
+    /// Steps of a replayed pipeline (--pipeline) come first.
+    /// Every step is validated, so the pipeline cannot fail later on.
+    pub fn from_cli(cli: &Cli) -> Result<Self, Error> {
+        let mut pipeline = match cli.pipeline_file() {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        let masks = [
+            (cli.gps_filter(), ConstellationMask::Gps),
+            (cli.glo_filter(), ConstellationMask::Glonass),
+            (cli.gal_filter(), ConstellationMask::Galileo),
+            (cli.bds_filter(), ConstellationMask::BeiDou),
+            (cli.bds_geo_filter(), ConstellationMask::BeiDouGeo),
+            (cli.sbas_filter(), ConstellationMask::Sbas),
+            (cli.qzss_filter(), ConstellationMask::Qzss),
+            (cli.irnss_filter(), ConstellationMask::Irnss),
+        ];
+
+        for (_, mask) in masks.iter().filter(|(enabled, _)| *enabled) {
+            pipeline.steps.push(Step::ConstellationMask(*mask));
+        }
+
+        // combinations derive from fully preprocessed observations
+        let mut combinations = Vec::new();
+
+        for desc in cli.preprocessing() {
+            let (scope, description) = Scope::split(desc);
+
+            if Combination::is_combination(description) {
+                if scope.map_or(false, |scope| scope != Scope::Observation) {
+                    return Err(Error::InvalidFilter(
+                        desc.to_string(),
+                        "only applies to Observations".to_string(),
+                    ));
+                }
+                combinations.push(Step::Combination(description.to_string()));
+                continue;
+            }
+
+            if let Some(mask) = SkyMask::from_filter(description) {
+                if scope.map_or(false, |scope| scope != Scope::Observation) {
+                    return Err(Error::InvalidFilter(
+                        desc.to_string(),
+                        "only applies to Observations".to_string(),
+                    ));
+                }
+                pipeline.steps.push(Step::SkyMask(mask?));
+                continue;
+            }
+
+            if SignalMask::is_signal_mask(description) {
+                if scope.map_or(false, |scope| scope != Scope::Observation) {
+                    return Err(Error::InvalidFilter(
+                        desc.to_string(),
+                        "only applies to Observations".to_string(),
+                    ));
+                }
+                pipeline
+                    .steps
+                    .push(Step::SignalMask(description.to_string()));
+                continue;
+            }
+
+            pipeline.steps.push(Step::Filter {
+                scope,
+                description: description.to_string(),
+            });
+        }
+
+        if let Some(path) = cli.terrain_mask_file() {
+            pipeline
+                .steps
+                .push(Step::SkyMask(SkyMask::terrain_from_file(path)?));
+        }
+
+        for desc in cli.nav_filters() {
+            pipeline
+                .steps
+                .push(Step::NavFilter(desc.trim().to_string()));
+        }
+
+        if cli.zero_repair() {
+            pipeline.steps.push(Step::ZeroRepair);
+        }
+
+        // clock jumps would be interpreted as outliers and cycle slips
+        if let Some(mode) = cli.clock_jumps() {
+            pipeline.steps.push(Step::ClockJumps(mode));
+        }
+
+        // outliers would bias the Melbourne-Wübbena combination
+        if let Some(opts) = cli.outliers() {
+            pipeline.steps.push(Step::Outliers(opts));
+        }
+
+        // zero values would be interpreted as cycle slips
+        if let Some(opts) = cli.cycle_slips() {
+            pipeline.steps.push(Step::CycleSlips(opts));
+        }
+
+        // flagged cycle slips reset the smoothing filter
+        if let Some(opts) = cli.code_smoothing() {
+            pipeline.steps.push(Step::CodeSmoothing(opts));
+        }
+
+        pipeline.steps.append(&mut combinations);
+
+        // every other step operates on actual samples
+        if let Some(opts) = cli.resampling() {
+            pipeline.steps.push(Step::Resampling(opts));
+        }
+
+        pipeline.validate()?;
+        Ok(pipeline)
+    }
+
+    /// Loads a [PreprocessingPipeline] previously serialized,
+    /// for example the "preprocessing" field of run.json.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let name = path.display().to_string();
+
+        let content = read_to_string(path).map_err(|e| Error::PipelineRead(name.clone(), e))?;
+
+        let pipeline: Self =
+            serde_json::from_str(&content).map_err(|e| Error::PipelineParsing(name, e))?;
+
+        pipeline.validate()?;
+        Ok(pipeline)
+    }
+
+    /// Validates every step
+    fn validate(&self) -> Result<(), Error> {
+        for step in self.steps.iter() {
+            step.op()?;
+        }
+        Ok(())
+    }
+
+    /// Returns all steps, in order of application
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// True if this pipeline requires the reference position
+    pub fn requires_rx_orbit(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| matches!(step, Step::SkyMask(_)))
+    }
+
+    /// Iterates over all validated ops, in order of application
+    fn ops(&self) -> impl Iterator<Item = (&Step, Op)> + '_ {
+        self.steps
+            .iter()
+            .filter_map(|step| step.op().ok().map(|op| (step, op)))
+    }
+
+    /// Applies this pipeline to mutable [QcContext].
+    /// Sky masks are computed from the reference position `rx_orbit`.
+    pub fn apply_context(
+        &self,
+        ctx: &mut QcContext,
+        rx_orbit: Option<&Orbit>,
+    ) -> PreprocessingReport {
+        let mut report = PreprocessingReport::default();
+
+        for (step, op) in self.ops() {
+            match op {
+                Op::Filters(None, filters) => {
+                    for filter in filters.iter() {
+                        ctx.filter_mut(filter);
+                    }
+                },
+                Op::Filters(Some(Scope::Sp3), filters) => {
+                    if let Some(sp3) = ctx.sp3_mut() {
+                        for filter in filters.iter() {
+                            sp3.filter_mut(filter);
+                        }
+                    }
+                },
+                Op::Filters(Some(scope), filters) => {
+                    if let Some(rinex) = ctx.rinex_mut(scope.product_type()) {
+                        for filter in filters.iter() {
+                            rinex.filter_mut(filter);
+                        }
+                    }
+                },
+                Op::SkyMask(mask) => match rx_orbit {
+                    Some(rx_orbit) => {
+                        let masked = sky_mask::process(ctx, rx_orbit, &mask);
+                        info!("{}: {} observation(s) masked", step, masked);
+                    },
+                    None => {
+                        error!(
+                            "{} skipped: no reference position (use --rx-ecef or --rx-geo)",
+                            step
+                        );
+                        continue;
+                    },
+                },
+                Op::SignalMask(mask) => {
+                    if let Some(observation) = ctx.observation_mut() {
+                        let dropped = signal_mask::process(observation, &mask);
+                        info!("{}: {} observation(s) dropped", step, dropped);
+                    }
+                },
+                #[cfg(feature = "ppp")]
+                Op::NavFilter(filter) => ctx.nav_filter_mut(&filter),
+                Op::ZeroRepair => ctx.repair_mut(Repair::Zero),
+                Op::ClockJumps(mode) => {
+                    if let Some(observation) = ctx.observation_mut() {
+                        let jumps = clock_jumps::process(observation, mode);
+                        report.add_clock_jumps(jumps);
+                    }
+                },
+                Op::Outliers(opts) => {
+                    if let Some(observation) = ctx.observation_mut() {
+                        let (rejected, masked) = outliers::process(observation, &opts);
+                        report.add_outliers(rejected, masked);
+                    }
+                },
+                Op::CycleSlips(opts) => {
+                    if let Some(observation) = ctx.observation_mut() {
+                        let slips = cycle_slips::process(observation, &opts);
+                        report.add_cycle_slips(opts.mode, slips);
+                    }
+                },
+                Op::CodeSmoothing(opts) => {
+                    if let Some(observation) = ctx.observation_mut() {
+                        let summaries = code_smoothing::process(observation, &opts);
+                        report.add_code_smoothing(summaries);
+                    }
+                },
+                Op::Combination(comb) => {
+                    if let Some(observation) = ctx.observation_mut() {
+                        let total = combinations::process(observation, &comb);
+                        info!("{}: {} samples", step, total);
+                    }
+                },
+                Op::Resampling(opts) => {
+                    for product in [
+                        ProductType::Observation,
+                        ProductType::MeteoObservation,
+                        ProductType::HighPrecisionClock,
+                    ] {
+                        if let Some(rinex) = ctx.rinex_mut(product) {
+                            let epochs = resampling::process(rinex, &opts);
+                            info!("{:?} {}: {} epochs", product, step, epochs);
+                        }
+                    }
+                },
+            }
+            debug!("applied {}", step);
+        }
+
+        report
+    }
+
+    /// Applies this pipeline to mutable [Rinex].
+    /// NAV filters only apply to a complete [QcContext].
+    pub fn apply_rinex(&self, rinex: &mut Rinex) -> PreprocessingReport {
+        let mut report = PreprocessingReport::default();
+        let product = rinex_product_type(rinex);
+
+        for (step, op) in self.ops() {
+            match op {
+                Op::Filters(scope, filters) => {
+                    if scope.map_or(true, |scope| scope.product_type() == product) {
+                        for filter in filters.iter() {
+                            rinex.filter_mut(filter);
+                        }
+                        debug!("applied {}", step);
+                    }
+                },
+                #[cfg(feature = "ppp")]
+                Op::NavFilter(_) => {
+                    debug!("{} skipped: only applies to complete contexts", step);
+                },
+                Op::SkyMask(_) => {
+                    debug!("{} skipped: only applies to complete contexts", step);
+                },
+                Op::SignalMask(mask) => {
+                    if product == ProductType::Observation {
+                        let dropped = signal_mask::process(rinex, &mask);
+                        debug!("applied {}: {} observation(s) dropped", step, dropped);
+                    }
+                },
+                Op::ZeroRepair => {
+                    rinex.repair_mut(Repair::Zero);
+                    debug!("applied {}", step);
+                },
+                Op::ClockJumps(mode) => {
+                    if product == ProductType::Observation {
+                        let jumps = clock_jumps::process(rinex, mode);
+                        report.add_clock_jumps(jumps);
+                        debug!("applied {}", step);
+                    }
+                },
+                Op::Outliers(opts) => {
+                    if product == ProductType::Observation {
+                        let (rejected, masked) = outliers::process(rinex, &opts);
+                        report.add_outliers(rejected, masked);
+                        debug!("applied {}", step);
+                    }
+                },
+                Op::CycleSlips(opts) => {
+                    if product == ProductType::Observation {
+                        let slips = cycle_slips::process(rinex, &opts);
+                        report.add_cycle_slips(opts.mode, slips);
+                        debug!("applied {}", step);
+                    }
+                },
+                Op::CodeSmoothing(opts) => {
+                    if product == ProductType::Observation {
+                        let summaries = code_smoothing::process(rinex, &opts);
+                        report.add_code_smoothing(summaries);
+                        debug!("applied {}", step);
+                    }
+                },
+                Op::Combination(comb) => {
+                    if product == ProductType::Observation {
+                        combinations::process(rinex, &comb);
+                        debug!("applied {}", step);
+                    }
+                },
+                Op::Resampling(opts) => {
+                    if matches!(
+                        product,
+                        ProductType::Observation
+                            | ProductType::MeteoObservation
+                            | ProductType::HighPrecisionClock
+                    ) {
+                        resampling::process(rinex, &opts);
+                        debug!("applied {}", step);
+                    }
+                },
+            }
+        }
+
+        report
+    }
+
+    /// Applies this pipeline to mutable [SP3].
+    /// Only filters apply to SP3.
+    pub fn apply_sp3(&self, sp3: &mut SP3) {
+        for (step, op) in self.ops() {
+            if let Op::Filters(scope, filters) = op {
+                if scope.map_or(true, |scope| scope == Scope::Sp3) {
+                    for filter in filters.iter() {
+                        sp3.filter_mut(filter);
+                    }
+                    debug!("applied {}", step);
+                }
+            }
+        }
+    }
+}
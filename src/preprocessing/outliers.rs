@@ -0,0 +1,392 @@
+//! Outliers rejection, based on robust statistics
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::Range,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use rinex::prelude::{Duration, Epoch, Observable, Rinex, SV};
+
+#[cfg(feature = "csv")]
+use csv::Writer;
+
+#[cfg(feature = "csv")]
+use std::path::Path;
+
+/// Scales the Median Absolute Deviation to the standard deviation
+/// of normally distributed data
+const MAD_SCALING: f64 = 1.4826;
+
+/// Masked samples: preserved in the record, but not used by the navigation solvers
+pub type MaskedSamples = HashSet<(Epoch, SV, Observable)>;
+
+/// Outliers detection method
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierMethod {
+    /// Median and Median Absolute Deviation of the complete time series
+    Mad,
+    /// Median and Median Absolute Deviation of a sliding window (Hampel filter)
+    Hampel,
+}
+
+impl FromStr for OutlierMethod {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mad" => Ok(Self::Mad),
+            "hampel" => Ok(Self::Hampel),
+            _ => Err(format!("unknown outliers method \"{}\"", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for OutlierMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Mad => write!(f, "MAD"),
+            Self::Hampel => write!(f, "Hampel"),
+        }
+    }
+}
+
+/// What to do with outliers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierAction {
+    /// Remove outliers
+    Drop,
+    /// Keep outliers untouched, but report them as [MaskedSamples]
+    Mask,
+}
+
+impl FromStr for OutlierAction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "drop" => Ok(Self::Drop),
+            "mask" => Ok(Self::Mask),
+            _ => Err(format!("unknown outliers action \"{}\"", s)),
+        }
+    }
+}
+
+/// Outliers rejection settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutlierOptions {
+    /// Detection method
+    pub method: OutlierMethod,
+    /// What to do with outliers
+    pub action: OutlierAction,
+    /// A sample is an outlier when it departs from the median
+    /// by more than this many (scaled) MADs
+    pub threshold: f64,
+    /// Half width of the sliding window, in samples, in [OutlierMethod::Hampel]
+    pub half_window: usize,
+}
+
+/// Rejected samples of one SV and observable
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedSamples {
+    pub sv: SV,
+    pub observable: Observable,
+    /// Total number of samples
+    pub samples: usize,
+    /// Number of outliers
+    pub rejected: usize,
+}
+
+/// Median of these values
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+
+    let mid = values.len() / 2;
+
+    if values.len() % 2 == 1 {
+        Some(values[mid])
+    } else {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    }
+}
+
+/// Returns the median and the scaled Median Absolute Deviation of these values
+fn median_mad(values: &[f64]) -> Option<(f64, f64)> {
+    let median = median(&mut values.to_vec())?;
+
+    let mut deviations = values
+        .iter()
+        .map(|value| (value - median).abs())
+        .collect::<Vec<_>>();
+
+    let mad = median(&mut deviations)?;
+    Some((median, mad * MAD_SCALING))
+}
+
+/// Splits this (sorted) time serie into continuous arcs:
+/// a new arc starts after any data gap larger than the sampling period.
+fn arcs(epochs: &[Epoch], sampling: Option<Duration>) -> Vec<Range<usize>> {
+    let mut arcs = Vec::new();
+    let mut start = 0;
+
+    for index in 1..epochs.len() {
+        if let Some(sampling) = sampling {
+            if epochs[index] - epochs[index - 1] > sampling {
+                arcs.push(start..index);
+                start = index;
+            }
+        }
+    }
+
+    if start < epochs.len() {
+        arcs.push(start..epochs.len());
+    }
+
+    arcs
+}
+
+/// Normalized deviations (value - median) / MAD of these arcs.
+/// [OutlierMethod::Mad] compares each value to the statistics of all arcs,
+/// [OutlierMethod::Hampel] to a sliding window that remains within its arc.
+/// None when there is no dispersion: nothing can be told.
+fn deviations(arcs: &[Vec<f64>], opts: &OutlierOptions) -> Vec<Vec<Option<f64>>> {
+    let normalize = |value: f64, (median, mad): (f64, f64)| {
+        if mad > 0.0 {
+            Some((value - median) / mad)
+        } else {
+            None
+        }
+    };
+
+    match opts.method {
+        OutlierMethod::Mad => {
+            let values = arcs.iter().flatten().copied().collect::<Vec<_>>();
+            let stats = median_mad(&values);
+
+            arcs.iter()
+                .map(|arc| {
+                    arc.iter()
+                        .map(|value| stats.and_then(|stats| normalize(*value, stats)))
+                        .collect()
+                })
+                .collect()
+        },
+        OutlierMethod::Hampel => arcs
+            .iter()
+            .map(|arc| {
+                (0..arc.len())
+                    .map(|index| {
+                        let start = index.saturating_sub(opts.half_window);
+                        let end = (index + opts.half_window + 1).min(arc.len());
+                        median_mad(&arc[start..end]).and_then(|stats| normalize(arc[index], stats))
+                    })
+                    .collect()
+            })
+            .collect(),
+    }
+}
+
+/// Second order differences x[i-1] - 2x[i] + x[i+1] of this arc, centered on i = 1..n-1.
+/// They remove the geometric ramp of a pseudo range (thousands of km over a pass,
+/// tens of km per epoch): only the range acceleration and the noise remain.
+fn second_differences(arc: &[f64]) -> Vec<f64> {
+    arc.windows(3)
+        .map(|window| window[0] - 2.0 * window[1] + window[2])
+        .collect()
+}
+
+/// Attributes outlying second differences to the samples of an arc of `n` samples.
+/// A wild sample `i` (error e) shows up as a (e, -2e, e) pattern centered on `i`.
+/// Arcs of less than 4 samples cannot be tested.
+fn attribute(n: usize, deviations: &[Option<f64>], threshold: f64) -> Vec<bool> {
+    if n < 4 {
+        return vec![false; n];
+    }
+
+    // deviation of the second difference centered on sample i
+    let dev = |i: usize| -> Option<f64> {
+        if i >= 1 && i + 1 < n {
+            deviations[i - 1]
+        } else {
+            None
+        }
+    };
+
+    let beyond = |i: usize, k: f64| dev(i).map_or(false, |dev| dev.abs() > k);
+
+    (0..n)
+        .map(|i| {
+            if i == 0 {
+                // only one neighbouring difference: it should not be explained by sample 1
+                beyond(1, threshold) && !beyond(2, threshold / 2.0)
+            } else if i == n - 1 {
+                beyond(n - 2, threshold) && !beyond(n - 3, threshold / 2.0)
+            } else {
+                let Some(center) = dev(i) else {
+                    return false;
+                };
+
+                center.abs() > threshold
+                    && [i - 1, i + 1]
+                        .into_iter()
+                        .filter(|j| *j >= 1 && *j + 1 < n)
+                        .all(|j| {
+                            dev(j).map_or(false, |dev| {
+                                dev.signum() != center.signum() && dev.abs() > threshold / 2.0
+                            })
+                        })
+            }
+        })
+        .collect()
+}
+
+/// Returns the index of all outliers in this time series, split into continuous arcs.
+/// Pseudo ranges are detrended first (refer to [second_differences]),
+/// other observables are tested directly.
+fn find_outliers(
+    observable: &Observable,
+    epochs: &[Epoch],
+    values: &[f64],
+    sampling: Option<Duration>,
+    opts: &OutlierOptions,
+) -> Vec<usize> {
+    let arcs = arcs(epochs, sampling);
+
+    if observable.is_pseudorange_observable() {
+        let differences = arcs
+            .iter()
+            .map(|arc| second_differences(&values[arc.clone()]))
+            .collect::<Vec<_>>();
+
+        let deviations = deviations(&differences, opts);
+
+        arcs.iter()
+            .zip(deviations.iter())
+            .flat_map(|(arc, deviations)| {
+                attribute(arc.len(), deviations, opts.threshold)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, outlier)| *outlier)
+                    .map(|(index, _)| arc.start + index)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    } else {
+        let series = arcs
+            .iter()
+            .map(|arc| values[arc.clone()].to_vec())
+            .collect::<Vec<_>>();
+
+        let deviations = deviations(&series, opts);
+
+        arcs.iter()
+            .zip(deviations.iter())
+            .flat_map(|(arc, deviations)| {
+                deviations
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, dev)| dev.map_or(false, |dev| dev.abs() > opts.threshold))
+                    .map(|(index, _)| arc.start + index)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Detects outliers in all time series (per SV and observable) of this
+/// Observation [Rinex], then drops or masks them. Phase observations are
+/// not concerned (refer to cycle slips).
+/// Returns the number of rejected samples, per SV and observable,
+/// and the [MaskedSamples] (always empty when outliers are dropped).
+pub fn process(rinex: &mut Rinex, opts: &OutlierOptions) -> (Vec<RejectedSamples>, MaskedSamples) {
+    let sampling = rinex.dominant_sampling_interval();
+
+    let mut series = BTreeMap::<(SV, Observable), (Vec<Epoch>, Vec<f64>)>::new();
+
+    for (k, v) in rinex.observations_iter() {
+        for signal in v.signals.iter() {
+            if signal.observable.is_phase_observable() {
+                continue;
+            }
+
+            let (epochs, values) = series
+                .entry((signal.sv, signal.observable.clone()))
+                .or_default();
+
+            epochs.push(k.epoch);
+            values.push(signal.value);
+        }
+    }
+
+    let mut rejected = Vec::new();
+    let mut outliers = MaskedSamples::new();
+
+    for ((sv, observable), (epochs, values)) in series.iter() {
+        let indexes = find_outliers(observable, epochs, values, sampling, opts);
+
+        if indexes.is_empty() {
+            continue;
+        }
+
+        info!(
+            "{}({}): {}/{} outliers",
+            sv,
+            observable,
+            indexes.len(),
+            values.len()
+        );
+
+        for index in indexes.iter() {
+            outliers.insert((epochs[*index], *sv, observable.clone()));
+        }
+
+        rejected.push(RejectedSamples {
+            sv: *sv,
+            observable: observable.clone(),
+            samples: values.len(),
+            rejected: indexes.len(),
+        });
+    }
+
+    if opts.action == OutlierAction::Mask || outliers.is_empty() {
+        return (rejected, outliers);
+    }
+
+    if let Some(record) = rinex.record.as_mut_obs() {
+        for (k, v) in record.iter_mut() {
+            v.signals.retain(|signal| {
+                !outliers.contains(&(k.epoch, signal.sv, signal.observable.clone()))
+            });
+        }
+    }
+
+    (rejected, MaskedSamples::new())
+}
+
+/// Writes the summary of rejected samples as CSV
+#[cfg(feature = "csv")]
+pub fn write_csv(rejected: &[RejectedSamples], path: &Path) -> Result<(), csv::Error> {
+    let mut w = Writer::from_path(path)?;
+    w.write_record(["SV", "Observable", "Samples", "Rejected", "Rejected [%]"])?;
+
+    for summary in rejected.iter() {
+        w.write_record(&[
+            summary.sv.to_string(),
+            summary.observable.to_string(),
+            summary.samples.to_string(),
+            summary.rejected.to_string(),
+            format!(
+                "{:.3}",
+                summary.rejected as f64 * 100.0 / summary.samples as f64
+            ),
+        ])?;
+    }
+
+    w.flush()?;
+    Ok(())
+}
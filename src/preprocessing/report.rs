@@ -0,0 +1,152 @@
+//! Preprocessing report
+use gnss_qc::prelude::QcExtraPage;
+use rinex::prelude::{Epoch, Observable, SV};
+
+use crate::{
+    cli::Workspace,
+    preprocessing::{
+        clock_jumps::{self, ClockJump},
+        code_smoothing::{self, SmoothedCode},
+        cycle_slips::{self, CycleSlip, CycleSlipMode},
+        outliers::{self, MaskedSamples, RejectedSamples},
+    },
+    summary::Summary,
+    Error,
+};
+
+/// Events detected while preprocessing one station,
+/// reported in the QC report and exported as CSV.
+#[derive(Debug, Default, Clone)]
+pub struct PreprocessingReport {
+    /// Detected cycle slips, when cycle slips detection is enabled
+    pub cycle_slips: Option<(CycleSlipMode, Vec<CycleSlip>)>,
+    /// Rejected samples, when outliers rejection is enabled
+    pub outliers: Option<Vec<RejectedSamples>>,
+    /// Outliers that were masked rather than dropped
+    pub masked: MaskedSamples,
+    /// Receiver clock jumps, when clock jumps detection is enabled
+    pub clock_jumps: Option<Vec<ClockJump>>,
+    /// Code smoothing summary, when code smoothing is enabled
+    pub code_smoothing: Option<Vec<SmoothedCode>>,
+}
+
+impl PreprocessingReport {
+    /// Stacks detected cycle slips
+    pub fn add_cycle_slips(&mut self, mode: CycleSlipMode, slips: Vec<CycleSlip>) {
+        info!("{} cycle slip(s) detected", slips.len());
+
+        match &mut self.cycle_slips {
+            Some((prev_mode, prev)) => {
+                *prev_mode = mode;
+                prev.extend(slips);
+            },
+            None => self.cycle_slips = Some((mode, slips)),
+        }
+    }
+
+    /// Stacks rejected samples, and masked samples when outliers are masked
+    pub fn add_outliers(&mut self, rejected: Vec<RejectedSamples>, masked: MaskedSamples) {
+        let total = rejected
+            .iter()
+            .map(|summary| summary.rejected)
+            .sum::<usize>();
+        info!("{} outlier(s) rejected", total);
+
+        self.outliers.get_or_insert_with(Vec::new).extend(rejected);
+        self.masked.extend(masked);
+    }
+
+    /// Returns true if this sample was masked by outliers rejection
+    pub fn is_masked(&self, t: Epoch, sv: SV, observable: &Observable) -> bool {
+        !self.masked.is_empty() && self.masked.contains(&(t, sv, observable.clone()))
+    }
+
+    /// Stacks receiver clock jumps
+    pub fn add_clock_jumps(&mut self, jumps: Vec<ClockJump>) {
+        info!("{} receiver clock jump(s) detected", jumps.len());
+        self.clock_jumps.get_or_insert_with(Vec::new).extend(jumps);
+    }
+
+    /// Stacks code smoothing summary
+    pub fn add_code_smoothing(&mut self, summaries: Vec<SmoothedCode>) {
+        let total = summaries
+            .iter()
+            .map(|summary| summary.resets)
+            .sum::<usize>();
+        info!("code smoothing: {} filter reset(s)", total);
+
+        self.code_smoothing
+            .get_or_insert_with(Vec::new)
+            .extend(summaries);
+    }
+
+    /// Returns the report pages of this [PreprocessingReport]
+    pub fn extra_pages(&self) -> Vec<QcExtraPage> {
+        let mut pages = Vec::new();
+
+        if let Some((mode, slips)) = &self.cycle_slips {
+            pages.push(cycle_slips::extra_page(*mode, slips));
+        }
+
+        pages
+    }
+
+    /// Exports this [PreprocessingReport] as CSV, in the Workspace
+    #[cfg(feature = "csv")]
+    pub fn write_csv(&self, workspace: &Workspace, summary: &Summary) -> Result<(), Error> {
+        if let Some((_, slips)) = &self.cycle_slips {
+            workspace.create_subdir("CSV")?;
+
+            let path = workspace.root.join("CSV").join("cycle_slips.csv");
+            cycle_slips::write_csv(slips, &path)?;
+
+            info!("cycle slips dumped in {}", path.display());
+            summary.output(&path);
+        }
+
+        if let Some(rejected) = &self.outliers {
+            workspace.create_subdir("CSV")?;
+
+            let path = workspace.root.join("CSV").join("outliers.csv");
+            outliers::write_csv(rejected, &path)?;
+
+            info!("outliers summary dumped in {}", path.display());
+            summary.output(&path);
+        }
+
+        if let Some(jumps) = &self.clock_jumps {
+            workspace.create_subdir("CSV")?;
+
+            let path = workspace.root.join("CSV").join("clock_jumps.csv");
+            clock_jumps::write_csv(jumps, &path)?;
+
+            info!("clock jumps dumped in {}", path.display());
+            summary.output(&path);
+        }
+
+        if let Some(summaries) = &self.code_smoothing {
+            workspace.create_subdir("CSV")?;
+
+            let path = workspace.root.join("CSV").join("code_smoothing.csv");
+            code_smoothing::write_csv(summaries, &path)?;
+
+            info!("code smoothing summary dumped in {}", path.display());
+            summary.output(&path);
+        }
+
+        Ok(())
+    }
+
+    /// Exports this [PreprocessingReport] as CSV, in the Workspace
+    #[cfg(not(feature = "csv"))]
+    pub fn write_csv(&self, _: &Workspace, _: &Summary) -> Result<(), Error> {
+        if self.cycle_slips.is_some()
+            || self.outliers.is_some()
+            || self.clock_jumps.is_some()
+            || self.code_smoothing.is_some()
+        {
+            warn!("CSV export requires the \"csv\" feature");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,363 @@
+//! Resampling onto a regular time grid
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use rinex::prelude::{Duration, Epoch, Observable, Rinex, TimeScale, SV};
+
+/// Interpolation method
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// Closest sample
+    Nearest,
+    /// Linear interpolation between the two surrounding samples
+    Linear,
+    /// Lagrange interpolation of the surrounding samples
+    Lagrange,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "nearest" => Ok(Self::Nearest),
+            "linear" => Ok(Self::Linear),
+            "lagrange" => Ok(Self::Lagrange),
+            _ => Err(format!("unknown interpolation method \"{}\"", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Nearest => write!(f, "nearest"),
+            Self::Linear => write!(f, "linear"),
+            Self::Lagrange => write!(f, "lagrange"),
+        }
+    }
+}
+
+/// Resampling settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResamplingOptions {
+    /// Grid period, in seconds. Grid epochs are multiples of this period in GPST.
+    pub period_s: f64,
+    /// Interpolation method
+    pub method: Interpolation,
+    /// Lagrange interpolation order, in [Interpolation::Lagrange]
+    pub order: usize,
+    /// No value is interpolated across a data gap larger than this, in seconds
+    pub max_gap_s: f64,
+}
+
+impl ResamplingOptions {
+    fn period(&self) -> Duration {
+        Duration::from_seconds(self.period_s)
+    }
+
+    fn max_gap(&self) -> Duration {
+        Duration::from_seconds(self.max_gap_s)
+    }
+}
+
+/// Regular grid covering [first, last], aligned to multiples of the period in GPST,
+/// expressed in the timescale of `first`.
+fn grid(first: Epoch, last: Epoch, opts: &ResamplingOptions) -> Vec<Epoch> {
+    let period = opts.period();
+    let mut t = first.to_time_scale(TimeScale::GPST).ceil(period);
+
+    let mut grid = Vec::new();
+
+    while t <= last {
+        grid.push(t.to_time_scale(first.time_scale));
+        t += period;
+    }
+
+    grid
+}
+
+/// Lagrange interpolation of these samples at `t`
+fn lagrange(samples: &[(Epoch, f64)], t: Epoch) -> f64 {
+    let mut value = 0.0;
+
+    for (i, (t_i, y_i)) in samples.iter().enumerate() {
+        let mut l_i = 1.0;
+        for (j, (t_j, _)) in samples.iter().enumerate() {
+            if i != j {
+                l_i *= (t - *t_j).to_seconds() / (*t_i - *t_j).to_seconds();
+            }
+        }
+        value += y_i * l_i;
+    }
+
+    value
+}
+
+/// Interpolates this time series (sorted in time) at `t`.
+/// Returns the interpolated value and the index of the closest sample,
+/// or None if `t` is not surrounded by samples closer than the maximal gap.
+fn interpolate(
+    samples: &[(Epoch, f64)],
+    t: Epoch,
+    opts: &ResamplingOptions,
+) -> Option<(f64, usize)> {
+    let max_gap = opts.max_gap();
+    let after = samples.partition_point(|(t_i, _)| *t_i < t);
+
+    if let Some((t_i, value)) = samples.get(after) {
+        if *t_i == t {
+            return Some((*value, after));
+        }
+    }
+
+    let before = after.checked_sub(1);
+
+    let nearest = match (before, samples.get(after)) {
+        (Some(before), Some((t_after, _))) => {
+            if t - samples[before].0 <= *t_after - t {
+                before
+            } else {
+                after
+            }
+        },
+        (Some(before), None) => before,
+        (None, Some(_)) => after,
+        (None, None) => return None,
+    };
+
+    match opts.method {
+        Interpolation::Nearest => {
+            let (t_i, value) = samples[nearest];
+            if (t - t_i).abs() <= max_gap {
+                Some((value, nearest))
+            } else {
+                None
+            }
+        },
+        Interpolation::Linear => {
+            let before = before?;
+            let (t_0, y_0) = samples[before];
+            let (t_1, y_1) = samples.get(after)?;
+
+            if *t_1 - t_0 > max_gap {
+                return None;
+            }
+
+            let alpha = (t - t_0).to_seconds() / (*t_1 - t_0).to_seconds();
+            Some((y_0 + (y_1 - y_0) * alpha, nearest))
+        },
+        Interpolation::Lagrange => {
+            // t needs to be surrounded
+            before?;
+            samples.get(after)?;
+
+            let size = opts.order + 1;
+            if samples.len() < size {
+                return None;
+            }
+
+            let start = after.saturating_sub(size / 2).min(samples.len() - size);
+
+            let window = &samples[start..start + size];
+
+            if window
+                .windows(2)
+                .any(|pair| pair[1].0 - pair[0].0 > max_gap)
+            {
+                return None;
+            }
+
+            Some((lagrange(window, t), nearest))
+        },
+    }
+}
+
+/// Resamples all observations of this Observation [Rinex].
+/// Epochs that are not flagged OK (events) are dropped.
+/// Flags (LLI, SNR) are copied from the closest sample.
+/// Returns the number of resampled epochs.
+fn resample_observations(rinex: &mut Rinex, opts: &ResamplingOptions) -> usize {
+    let record = match rinex.record.as_mut_obs() {
+        Some(record) => record,
+        None => return 0,
+    };
+
+    let original = std::mem::take(record);
+
+    let epochs = original
+        .iter()
+        .filter(|(k, _)| k.flag.is_ok())
+        .collect::<Vec<_>>();
+
+    let bounds = epochs
+        .first()
+        .zip(epochs.last())
+        .map(|((first, _), (last, _))| (first.epoch, last.epoch));
+
+    let Some((first, last)) = bounds else {
+        drop(epochs);
+        *record = original;
+        return 0;
+    };
+
+    // one time series per SV and observable,
+    // with the (epoch, signal) index of each sample
+    let mut series = BTreeMap::<(SV, Observable), (Vec<(Epoch, f64)>, Vec<(usize, usize)>)>::new();
+    let mut clock = Vec::<(Epoch, f64)>::new();
+
+    for (index, (k, v)) in epochs.iter().enumerate() {
+        for (nth, signal) in v.signals.iter().enumerate() {
+            let (samples, templates) = series
+                .entry((signal.sv, signal.observable.clone()))
+                .or_default();
+
+            samples.push((k.epoch, signal.value));
+            templates.push((index, nth));
+        }
+
+        if let Some(clk) = &v.clock {
+            clock.push((k.epoch, clk.offset_s));
+        }
+    }
+
+    let mut resampled = BTreeMap::new();
+
+    for t in grid(first, last, opts) {
+        // epoch attributes are copied from the closest epoch
+        let closest = epochs.partition_point(|(k, _)| k.epoch < t);
+        let closest = match (closest.checked_sub(1), epochs.get(closest)) {
+            (Some(before), Some((k, _))) => {
+                if t - epochs[before].0.epoch <= k.epoch - t {
+                    before
+                } else {
+                    closest
+                }
+            },
+            (Some(before), None) => before,
+            (None, _) => closest,
+        };
+
+        let (k, v) = epochs[closest];
+
+        let mut key = k.clone();
+        key.epoch = t;
+
+        let mut observations = v.clone();
+        observations.signals.clear();
+
+        observations.clock = match (&v.clock, interpolate(&clock, t, opts)) {
+            (Some(clk), Some((offset_s, _))) => {
+                let mut clk = clk.clone();
+                clk.offset_s = offset_s;
+                Some(clk)
+            },
+            _ => None,
+        };
+
+        for (samples, templates) in series.values() {
+            if let Some((value, nearest)) = interpolate(samples, t, opts) {
+                let (index, nth) = templates[nearest];
+                let (_, v) = epochs[index];
+
+                let mut signal = v.signals[nth].clone();
+                signal.value = value;
+                observations.signals.push(signal);
+            }
+        }
+
+        if !observations.signals.is_empty() {
+            resampled.insert(key, observations);
+        }
+    }
+
+    let size = resampled.len();
+    *record = resampled;
+    size
+}
+
+/// Resamples a record made of one value per key, where keys are
+/// identical but for their [Epoch].
+fn resample_map<K: Ord + Clone, V: Clone>(
+    record: &mut BTreeMap<K, V>,
+    epoch: impl Fn(&K) -> Epoch,
+    with_epoch: impl Fn(&K, Epoch) -> K,
+    value: impl Fn(&V) -> f64,
+    with_value: impl Fn(&V, f64) -> V,
+    opts: &ResamplingOptions,
+) -> usize {
+    let original = std::mem::take(record);
+
+    let first = original.keys().map(&epoch).min();
+    let last = original.keys().map(&epoch).max();
+
+    let Some((first, last)) = first.zip(last) else {
+        *record = original;
+        return 0;
+    };
+
+    // one time series per key (epoch excluded)
+    let mut series = BTreeMap::<K, (Vec<(Epoch, f64)>, Vec<&V>)>::new();
+
+    for (k, v) in original.iter() {
+        let (samples, templates) = series.entry(with_epoch(k, first)).or_default();
+        samples.push((epoch(k), value(v)));
+        templates.push(v);
+    }
+
+    let grid = grid(first, last, opts);
+
+    for (id, (samples, templates)) in series.iter() {
+        for t in grid.iter() {
+            if let Some((interpolated, nearest)) = interpolate(samples, *t, opts) {
+                record.insert(
+                    with_epoch(id, *t),
+                    with_value(templates[nearest], interpolated),
+                );
+            }
+        }
+    }
+
+    grid.len()
+}
+
+/// Resamples this Observation, Meteo or Clock [Rinex] onto a regular grid.
+/// Returns the number of grid epochs.
+pub fn process(rinex: &mut Rinex, opts: &ResamplingOptions) -> usize {
+    if rinex.is_observation_rinex() {
+        resample_observations(rinex, opts)
+    } else if let Some(record) = rinex.record.as_mut_meteo() {
+        resample_map(
+            record,
+            |k| k.epoch,
+            |k, t| {
+                let mut k = k.clone();
+                k.epoch = t;
+                k
+            },
+            |v| *v,
+            |_, value| value,
+            opts,
+        )
+    } else if let Some(record) = rinex.record.as_mut_clock() {
+        resample_map(
+            record,
+            |k| k.epoch,
+            |k, t| {
+                let mut k = k.clone();
+                k.epoch = t;
+                k
+            },
+            |v| v.bias,
+            |v, bias| {
+                let mut v = v.clone();
+                v.bias = bias;
+                v
+            },
+            opts,
+        )
+    } else {
+        0
+    }
+}
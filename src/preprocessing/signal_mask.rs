@@ -0,0 +1,190 @@
+//! Signal strength masks: SNR flags and C/N0 (S observables)
+use std::{collections::HashSet, str::FromStr};
+
+use gnss_qc::prelude::MaskOperand as QcMaskOperand;
+
+use rinex::prelude::{obs::SNR, Carrier, Constellation, Rinex};
+
+/// Signal strength threshold
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Threshold {
+    /// SNR flag, as reported by the receiver, per observation
+    Snr(SNR),
+    /// Carrier to noise density ratio in dB-Hz, from the S observable of each carrier
+    Cn0(f64),
+}
+
+/// Masks weak signals, possibly restricted to some constellations and carriers.
+/// Descriptions look like "snr>=5", "cn0>35", "L2,L5:cn0>30" or "GPS:L5:snr>6".
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalMask {
+    /// Constellations this mask applies to (all if empty)
+    constellations: Vec<Constellation>,
+    /// Carriers this mask applies to (all if empty)
+    carriers: Vec<Carrier>,
+    /// Signals that do not verify this condition are dropped
+    operand: QcMaskOperand,
+    threshold: Threshold,
+}
+
+impl SignalMask {
+    /// True if this `-P` filter description describes a [SignalMask]
+    pub fn is_signal_mask(desc: &str) -> bool {
+        let condition = desc.rsplit(':').next().unwrap_or(desc);
+        let condition = condition.trim().to_lowercase();
+        condition.starts_with("snr") || condition.starts_with("cn0")
+    }
+
+    /// True if this [SignalMask] applies to this signal
+    fn applies(&self, constellation: Constellation, carrier: Carrier) -> bool {
+        (self.constellations.is_empty() || self.constellations.contains(&constellation))
+            && (self.carriers.is_empty() || self.carriers.contains(&carrier))
+    }
+
+    /// True if this value verifies the condition
+    fn verifies<T: PartialOrd>(&self, value: T, threshold: T) -> bool {
+        match self.operand {
+            QcMaskOperand::GreaterThan => value > threshold,
+            QcMaskOperand::GreaterEquals => value >= threshold,
+            QcMaskOperand::LowerThan => value < threshold,
+            QcMaskOperand::LowerEquals => value <= threshold,
+            QcMaskOperand::Equals => value == threshold,
+            QcMaskOperand::NotEquals => value != threshold,
+        }
+    }
+}
+
+impl FromStr for SignalMask {
+    type Err = String;
+    fn from_str(desc: &str) -> Result<Self, Self::Err> {
+        let mut items = desc.split(':').map(|item| item.trim()).collect::<Vec<_>>();
+
+        let condition = items
+            .pop()
+            .ok_or_else(|| "missing condition".to_string())?
+            .to_lowercase();
+
+        let mut constellations = Vec::new();
+        let mut carriers = Vec::new();
+
+        for item in items.iter().flat_map(|items| items.split(',')) {
+            let item = item.trim();
+            if let Ok(constellation) = Constellation::from_str(item) {
+                constellations.push(constellation);
+            } else if let Ok(carrier) = Carrier::from_str(item) {
+                carriers.push(carrier);
+            } else {
+                return Err(format!(
+                    "\"{}\" is neither a constellation nor a carrier",
+                    item
+                ));
+            }
+        }
+
+        let (is_snr, condition) = if let Some(condition) = condition.strip_prefix("snr") {
+            (true, condition.trim())
+        } else if let Some(condition) = condition.strip_prefix("cn0") {
+            (false, condition.trim())
+        } else {
+            return Err("expecting \"snr\" or \"cn0\" condition".to_string());
+        };
+
+        let (operand, value) = [
+            (">=", QcMaskOperand::GreaterEquals),
+            ("<=", QcMaskOperand::LowerEquals),
+            ("!=", QcMaskOperand::NotEquals),
+            (">", QcMaskOperand::GreaterThan),
+            ("<", QcMaskOperand::LowerThan),
+            ("=", QcMaskOperand::Equals),
+        ]
+        .into_iter()
+        .find_map(|(symbol, operand)| {
+            condition
+                .strip_prefix(symbol)
+                .map(|value| (operand, value.trim()))
+        })
+        .ok_or_else(|| "missing operand".to_string())?;
+
+        let threshold = if is_snr {
+            let snr =
+                SNR::from_str(value).map_err(|_| format!("invalid SNR flag \"{}\"", value))?;
+            Threshold::Snr(snr)
+        } else {
+            let cn0 = value
+                .parse::<f64>()
+                .map_err(|_| format!("invalid C/N0 \"{}\"", value))?;
+            Threshold::Cn0(cn0)
+        };
+
+        Ok(Self {
+            constellations,
+            carriers,
+            operand,
+            threshold,
+        })
+    }
+}
+
+/// Drops all signals of this Observation [Rinex] that do not verify this [SignalMask].
+/// SNR flags apply to each observation. C/N0 applies to all observations
+/// of the same carrier (pseudo range, phase, doppler and S observable).
+/// Observations without SNR flag or S observable are retained.
+/// Returns the number of dropped observations.
+pub fn process(rinex: &mut Rinex, mask: &SignalMask) -> usize {
+    let record = match rinex.record.as_mut_obs() {
+        Some(record) => record,
+        None => return 0,
+    };
+
+    let mut dropped = 0;
+
+    for (_, v) in record.iter_mut() {
+        // carriers whose C/N0 does not verify the mask
+        let weak = match mask.threshold {
+            Threshold::Cn0(threshold) => v
+                .signals
+                .iter()
+                .filter(|signal| signal.observable.is_ssi_observable())
+                .filter_map(|signal| {
+                    let carrier =
+                        Carrier::from_observable(signal.sv.constellation, &signal.observable)
+                            .ok()?;
+
+                    if mask.applies(signal.sv.constellation, carrier)
+                        && !mask.verifies(signal.value, threshold)
+                    {
+                        Some((signal.sv, carrier))
+                    } else {
+                        None
+                    }
+                })
+                .collect::<HashSet<_>>(),
+            Threshold::Snr(_) => HashSet::new(),
+        };
+
+        let total = v.signals.len();
+
+        v.signals.retain(|signal| {
+            let carrier =
+                match Carrier::from_observable(signal.sv.constellation, &signal.observable) {
+                    Ok(carrier) => carrier,
+                    Err(_) => return true,
+                };
+
+            if !mask.applies(signal.sv.constellation, carrier) {
+                return true;
+            }
+
+            match mask.threshold {
+                Threshold::Cn0(_) => !weak.contains(&(signal.sv, carrier)),
+                Threshold::Snr(threshold) => {
+                    signal.snr.map_or(true, |snr| mask.verifies(snr, threshold))
+                },
+            }
+        });
+
+        dropped += total - v.signals.len();
+    }
+
+    dropped
+}
@@ -0,0 +1,391 @@
+//! Elevation, azimuth and terrain masks, computed from the SV line of sight
+use std::{
+    collections::{HashMap, HashSet},
+    fs::read_to_string,
+    path::Path,
+};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use anise::prelude::Orbit;
+use gnss_qc::prelude::QcContext;
+
+use rinex::{
+    navigation::Ephemeris,
+    prelude::{Epoch, SV},
+};
+
+use crate::preprocessing::Error;
+
+/// Masks the Observations depending on the SV line of sight,
+/// as seen from the reference position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkyMask {
+    /// Retains lines of sight whose elevation is within this range (inclusive), in degrees
+    Elevation { min_deg: f64, max_deg: f64 },
+    /// Retains lines of sight whose azimuth is within this range (inclusive), in degrees.
+    /// The range wraps around the North when `min_deg` > `max_deg`.
+    Azimuth { min_deg: f64, max_deg: f64 },
+    /// Terrain (obstruction) mask: (azimuth, minimum elevation) table, in degrees,
+    /// sorted by azimuth. The minimum elevation is interpolated in between.
+    Terrain(Vec<(f64, f64)>),
+}
+
+impl std::fmt::Display for SkyMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Elevation { min_deg, max_deg } => {
+                write!(f, "elevation mask [{}°, {}°]", min_deg, max_deg)
+            },
+            Self::Azimuth { min_deg, max_deg } => {
+                write!(f, "azimuth mask [{}°, {}°]", min_deg, max_deg)
+            },
+            Self::Terrain(table) => write!(f, "terrain mask ({} points)", table.len()),
+        }
+    }
+}
+
+impl SkyMask {
+    /// Parses a `-P` filter description, returns None if this is not a [SkyMask].
+    /// Supported descriptions are "elev>10", "elev<=80" and "azim:90..180".
+    pub fn from_filter(desc: &str) -> Option<Result<Self, Error>> {
+        let invalid = |reason: &str| Error::InvalidFilter(desc.to_string(), reason.to_string());
+
+        if let Some(condition) = desc.strip_prefix("elev") {
+            let condition = condition.trim();
+
+            let (is_min, value) = if let Some(value) = condition.strip_prefix(">=") {
+                (true, value)
+            } else if let Some(value) = condition.strip_prefix("<=") {
+                (false, value)
+            } else if let Some(value) = condition.strip_prefix('>') {
+                (true, value)
+            } else if let Some(value) = condition.strip_prefix('<') {
+                (false, value)
+            } else {
+                return Some(Err(invalid("expecting \"elev>angle\" or \"elev<angle\"")));
+            };
+
+            let angle = match value.trim().parse::<f64>() {
+                Ok(angle) => angle,
+                Err(_) => return Some(Err(invalid("invalid elevation angle"))),
+            };
+
+            return Some(Ok(if is_min {
+                Self::Elevation {
+                    min_deg: angle,
+                    max_deg: 90.0,
+                }
+            } else {
+                Self::Elevation {
+                    min_deg: -90.0,
+                    max_deg: angle,
+                }
+            }));
+        }
+
+        if let Some(range) = desc.strip_prefix("azim") {
+            let range = range.trim().trim_start_matches(':');
+
+            let (min, max) = match range.split_once("..") {
+                Some(bounds) => bounds,
+                None => return Some(Err(invalid("expecting \"azim:min..max\""))),
+            };
+
+            return match (min.trim().parse::<f64>(), max.trim().parse::<f64>()) {
+                (Ok(min_deg), Ok(max_deg)) => Some(Ok(Self::Azimuth { min_deg, max_deg })),
+                _ => Some(Err(invalid("invalid azimuth angle"))),
+            };
+        }
+
+        None
+    }
+
+    /// Loads a [SkyMask::Terrain] table. Each line is made of one azimuth
+    /// and one minimum elevation angle, in degrees, separated by whitespaces or a comma.
+    /// Empty lines and lines starting with '#' are ignored.
+    pub fn terrain_from_file(path: &Path) -> Result<Self, Error> {
+        let name = path.display().to_string();
+
+        let content = read_to_string(path).map_err(|e| Error::TerrainMaskRead(name.clone(), e))?;
+
+        let mut table = Vec::new();
+
+        for (nth, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>();
+
+            match values.as_deref() {
+                Ok([azim, elev]) => table.push((*azim, *elev)),
+                _ => {
+                    return Err(Error::TerrainMaskParsing(
+                        name,
+                        format!("line {}: expecting \"azimuth elevation\"", nth + 1),
+                    ))
+                },
+            }
+        }
+
+        table.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self::Terrain(table))
+    }
+
+    /// Verifies this [SkyMask] is consistent
+    pub fn validate(&self) -> Result<(), String> {
+        let elevation = |angle: f64| (-90.0..=90.0).contains(&angle);
+        let azimuth = |angle: f64| (0.0..=360.0).contains(&angle);
+
+        match self {
+            Self::Elevation { min_deg, max_deg } => {
+                if !elevation(*min_deg) || !elevation(*max_deg) {
+                    return Err("elevation should be within [-90°, 90°]".to_string());
+                }
+                if min_deg > max_deg {
+                    return Err("empty elevation range".to_string());
+                }
+            },
+            Self::Azimuth { min_deg, max_deg } => {
+                if !azimuth(*min_deg) || !azimuth(*max_deg) {
+                    return Err("azimuth should be within [0°, 360°]".to_string());
+                }
+            },
+            Self::Terrain(table) => {
+                if table.is_empty() {
+                    return Err("empty terrain mask".to_string());
+                }
+                if table
+                    .iter()
+                    .any(|(azim, elev)| !azimuth(*azim) || !elevation(*elev))
+                {
+                    return Err("terrain mask angles out of range".to_string());
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Minimum elevation of the terrain, at this azimuth, linearly interpolated
+    /// between the two closest entries (wrapping around the North).
+    fn terrain_elevation(table: &[(f64, f64)], azim_deg: f64) -> f64 {
+        let after = table.partition_point(|(azim, _)| *azim < azim_deg);
+
+        let (before, after) = match after {
+            0 => (table[table.len() - 1], table[0]),
+            n if n == table.len() => (table[n - 1], table[0]),
+            n => (table[n - 1], table[n]),
+        };
+
+        // wrap around the North
+        let mut span = after.0 - before.0;
+        let mut offset = azim_deg - before.0;
+
+        if span <= 0.0 {
+            span += 360.0;
+        }
+        if offset < 0.0 {
+            offset += 360.0;
+        }
+
+        if span >= 360.0 {
+            before.1
+        } else {
+            before.1 + (after.1 - before.1) * offset / span
+        }
+    }
+
+    /// True if this line of sight should be retained
+    fn retains(&self, azim_deg: f64, elev_deg: f64) -> bool {
+        match self {
+            Self::Elevation { min_deg, max_deg } => elev_deg >= *min_deg && elev_deg <= *max_deg,
+            Self::Azimuth { min_deg, max_deg } => {
+                if min_deg <= max_deg {
+                    azim_deg >= *min_deg && azim_deg <= *max_deg
+                } else {
+                    azim_deg >= *min_deg || azim_deg <= *max_deg
+                }
+            },
+            Self::Terrain(table) => elev_deg >= Self::terrain_elevation(table, azim_deg),
+        }
+    }
+}
+
+/// SV positions in km ECEF, obtained from SP3 (preferred) or broadcast ephemeris.
+struct SvPositions {
+    /// Precise positions, sorted in time
+    precise: HashMap<SV, Vec<(Epoch, (f64, f64, f64))>>,
+    /// Broadcast ephemeris, with their time of clock
+    ephemeris: HashMap<SV, Vec<(Epoch, Ephemeris)>>,
+}
+
+impl SvPositions {
+    fn new(ctx: &QcContext) -> Self {
+        let mut precise = HashMap::<SV, Vec<(Epoch, (f64, f64, f64))>>::new();
+        let mut ephemeris = HashMap::<SV, Vec<(Epoch, Ephemeris)>>::new();
+
+        if let Some(sp3) = ctx.sp3() {
+            for (t, sv, _, _, position_km) in sp3.satellites_position_km_iter() {
+                precise.entry(sv).or_default().push((t, position_km));
+            }
+            for positions in precise.values_mut() {
+                positions.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+        }
+
+        if let Some(brdc) = ctx.brdc_navigation() {
+            for (k, v) in brdc.nav_ephemeris_frames_iter() {
+                ephemeris
+                    .entry(k.sv)
+                    .or_default()
+                    .push((k.epoch, v.clone()));
+            }
+        }
+
+        Self { precise, ephemeris }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.precise.is_empty() && self.ephemeris.is_empty()
+    }
+
+    /// Precise position, linearly interpolated in between two SP3 epochs
+    fn precise_position_km(&self, t: Epoch, sv: SV) -> Option<(f64, f64, f64)> {
+        let positions = self.precise.get(&sv)?;
+        let after = positions.partition_point(|(t_i, _)| *t_i < t);
+
+        let (t1, p1) = positions.get(after)?;
+        if *t1 == t {
+            return Some(*p1);
+        }
+
+        let (t0, p0) = positions.get(after.checked_sub(1)?)?;
+        let alpha = (t - *t0).to_seconds() / (*t1 - *t0).to_seconds();
+
+        Some((
+            p0.0 + (p1.0 - p0.0) * alpha,
+            p0.1 + (p1.1 - p0.1) * alpha,
+            p0.2 + (p1.2 - p0.2) * alpha,
+        ))
+    }
+
+    /// Keplerian position, from the closest valid ephemeris
+    fn keplerian_position_km(&self, t: Epoch, sv: SV) -> Option<(f64, f64, f64)> {
+        let (_, eph) = self
+            .ephemeris
+            .get(&sv)?
+            .iter()
+            .filter(|(_, eph)| eph.is_valid(sv, t))
+            .min_by_key(|(toc, _)| (t - *toc).abs())?;
+
+        let orbit = eph.kepler2position(sv, t)?;
+        let state = orbit.to_cartesian_pos_vel();
+        Some((state[0], state[1], state[2]))
+    }
+
+    fn position_km(&self, t: Epoch, sv: SV) -> Option<(f64, f64, f64)> {
+        self.precise_position_km(t, sv)
+            .or_else(|| self.keplerian_position_km(t, sv))
+    }
+}
+
+/// Returns (azimuth, elevation) in degrees of the line of sight
+/// from the reference position to this SV position (both in km ECEF).
+fn azimuth_elevation_deg(
+    rx_km: (f64, f64, f64),
+    (lat_ddeg, long_ddeg): (f64, f64),
+    sv_km: (f64, f64, f64),
+) -> (f64, f64) {
+    let (dx, dy, dz) = (sv_km.0 - rx_km.0, sv_km.1 - rx_km.1, sv_km.2 - rx_km.2);
+
+    let (sin_lat, cos_lat) = lat_ddeg.to_radians().sin_cos();
+    let (sin_long, cos_long) = long_ddeg.to_radians().sin_cos();
+
+    // local East, North, Up
+    let e = -sin_long * dx + cos_long * dy;
+    let n = -sin_lat * cos_long * dx - sin_lat * sin_long * dy + cos_lat * dz;
+    let u = cos_lat * cos_long * dx + cos_lat * sin_long * dy + sin_lat * dz;
+
+    let elev_deg = u.atan2((e.powi(2) + n.powi(2)).sqrt()).to_degrees();
+    let azim_deg = e.atan2(n).to_degrees().rem_euclid(360.0);
+
+    (azim_deg, elev_deg)
+}
+
+/// Applies this [SkyMask] to the Observations of this [QcContext],
+/// as seen from the reference position.
+/// Observations of SV whose position cannot be determined are retained.
+/// Returns the number of masked (epoch, SV) pairs.
+pub fn process(ctx: &mut QcContext, rx_orbit: &Orbit, mask: &SkyMask) -> usize {
+    let (lat_ddeg, long_ddeg, _) = match rx_orbit.latlongalt() {
+        Ok(geodetic) => geodetic,
+        Err(e) => {
+            error!("reference point - physical error: {}", e);
+            return 0;
+        },
+    };
+
+    let state = rx_orbit.to_cartesian_pos_vel();
+    let rx_km = (state[0], state[1], state[2]);
+
+    let masked = {
+        let observation = match ctx.observation() {
+            Some(observation) => observation,
+            None => return 0,
+        };
+
+        let positions = SvPositions::new(ctx);
+
+        if positions.is_empty() {
+            error!("{}: requires SP3 or NAV RINEX", mask);
+            return 0;
+        }
+
+        let mut masked = HashSet::<(Epoch, SV)>::new();
+        let mut unknown = HashSet::<SV>::new();
+
+        for (k, v) in observation.observations_iter() {
+            for sv in v.signals.iter().map(|signal| signal.sv).unique() {
+                match positions.position_km(k.epoch, sv) {
+                    Some(sv_km) => {
+                        let (azim_deg, elev_deg) =
+                            azimuth_elevation_deg(rx_km, (lat_ddeg, long_ddeg), sv_km);
+
+                        if !mask.retains(azim_deg, elev_deg) {
+                            masked.insert((k.epoch, sv));
+                        }
+                    },
+                    None => {
+                        unknown.insert(sv);
+                    },
+                }
+            }
+        }
+
+        for sv in unknown.iter().sorted() {
+            debug!("{}: undetermined position, not masked by {}", sv, mask);
+        }
+
+        masked
+    };
+
+    if let Some(record) = ctx
+        .observation_mut()
+        .and_then(|observation| observation.record.as_mut_obs())
+    {
+        for (k, v) in record.iter_mut() {
+            v.signals
+                .retain(|signal| !masked.contains(&(k.epoch, signal.sv)));
+        }
+    }
+
+    masked.len()
+}
@@ -1,5 +1,5 @@
 //! Analysis report
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
 
 use std::{
     fs::{read_to_string, File},
@@ -7,7 +7,7 @@ use std::{
     //io::Read,
 };
 
-use crate::cli::{Cli, Context};
+use crate::cli::{Cli, Context, Fingerprint};
 
 use gnss_qc::prelude::{QcConfig, QcExtraPage, QcReport, Render};
 
@@ -27,38 +27,45 @@ impl Report {
         } else {
             ctx.workspace.root.join("index.html")
         };
-        let hash_path = ctx.workspace.root.join(".hash");
-        if !cli.force_report_synthesis() && report_path.exists() && hash_path.exists() {
-            // determine whether we can preserve previous report or not
-            if let Ok(content) = read_to_string(hash_path) {
-                if let Ok(prev_hash) = content.parse::<u64>() {
-                    if prev_hash == cli.hash() {
-                        if let Ok(content) = read_to_string(report_path) {
-                            info!("preserving previous report");
-                            Self::Iteration(content)
-                        } else {
-                            info!("generating new report");
-                            Self::Pending(QcReport::new(&ctx.data, cfg))
-                        }
-                    } else {
-                        info!("generating new report");
-                        Self::Pending(QcReport::new(&ctx.data, cfg))
-                    }
-                } else {
-                    error!("failed to parse hashed value");
-                    warn!("forcing new report synthesis");
-                    Self::Pending(QcReport::new(&ctx.data, cfg))
-                }
-            } else {
-                // new report
-                info!("report synthesis");
-                Self::Pending(QcReport::new(&ctx.data, cfg))
-            }
-        } else {
+
+        if cli.force_report_synthesis() || !report_path.exists() {
             // new report
             info!("report synthesis");
-            Self::Pending(QcReport::new(&ctx.data, cfg))
+            return Self::Pending(QcReport::new(&ctx.data, cfg));
+        }
+
+        let explain = |reason: &str| {
+            if cli.explain_cache() {
+                info!("{}", reason);
+            } else {
+                debug!("{}", reason);
+            }
+        };
+
+        // determine whether we can preserve previous report or not
+        let fingerprint = cli.fingerprint(ctx);
+        let hash_path = ctx.workspace.root.join(".hash");
+
+        let differences = match Fingerprint::from_file(&hash_path) {
+            Some(previous) => fingerprint.differences(&previous),
+            None => vec!["no previous fingerprint".to_string()],
+        };
+
+        if differences.is_empty() {
+            if let Ok(content) = read_to_string(&report_path) {
+                explain("options and input files did not change");
+                info!("preserving previous report");
+                return Self::Iteration(content);
+            }
+            error!("failed to read previous report");
+        }
+
+        for difference in differences.iter() {
+            explain(difference);
         }
+
+        info!("generating new report");
+        Self::Pending(QcReport::new(&ctx.data, cfg))
     }
     /// Customize report with extra page
     pub fn customize(&mut self, page: QcExtraPage) {
@@ -93,7 +100,7 @@ impl Report {
                     }
                 } else {
                     // first run
-                    for known_chapter in ["ppp", "cggtts"] {
+                    for known_chapter in ["ppp", "cggtts", "cycle-slips", "diff"] {
                         let pattern = format!(
                             "<div id=\"end:{}\" style=\"display:none\"></div>",
                             known_chapter
@@ -137,10 +144,13 @@ impl Report {
         let mut fd = File::create(&path)?;
         write!(fd, "{}", html)?;
         info!("{} report generated", path.display());
+        ctx.summary.output(&path);
 
         // store past settings
-        if let Ok(mut fd) = File::create(ctx.workspace.root.join(".hash")) {
-            let _ = write!(fd, "{}", cli.hash());
+        let fingerprint = cli.fingerprint(ctx);
+
+        if let Err(e) = fingerprint.store(&ctx.workspace.root.join(".hash")) {
+            warn!("failed to store session fingerprint: {}", e);
         }
 
         Ok(())
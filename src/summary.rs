@@ -0,0 +1,205 @@
+//! Machine readable run summary (run.json)
+use std::{
+    cell::RefCell,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use env_logger::Logger;
+use lazy_static::lazy_static;
+use log::{Level, Log, Metadata, Record};
+use serde::Serialize;
+
+use gnss_qc::prelude::{Epoch, ProductType};
+
+use crate::{preprocessing::Step, Error};
+
+lazy_static! {
+    /// Warnings and errors emitted since last [take_logs]
+    static ref LOGS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+}
+
+/// [Log]ger that forwards to [env_logger], and keeps a copy of all
+/// warnings and errors, so they can be reported in the [RunSummary].
+pub struct Recorder {
+    inner: Logger,
+}
+
+impl Recorder {
+    /// Installs this [Recorder] as the global logger
+    pub fn init(inner: Logger) {
+        let max_level = inner.filter();
+        if log::set_boxed_logger(Box::new(Self { inner })).is_ok() {
+            log::set_max_level(max_level);
+        }
+    }
+}
+
+impl Log for Recorder {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= Level::Warn {
+            if let Ok(mut logs) = LOGS.lock() {
+                logs.push((record.level(), record.args().to_string()));
+            }
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Returns (and forgets) all warnings and errors emitted so far
+pub fn take_logs() -> Vec<(Level, String)> {
+    match LOGS.lock() {
+        Ok(mut logs) => std::mem::take(&mut *logs),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// One input file
+#[derive(Debug, Clone, Serialize)]
+pub struct InputFile {
+    /// File path
+    pub path: PathBuf,
+    /// Detected product type
+    pub product: String,
+}
+
+impl InputFile {
+    pub fn new(path: &Path, product: ProductType) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            product: product.to_string(),
+        }
+    }
+}
+
+/// Content of run.json
+#[derive(Debug, Default, Serialize)]
+pub struct RunSummary {
+    /// rinex-cli version
+    pub version: String,
+    /// Session (workspace) name
+    pub session: String,
+    /// Selected opmode
+    pub opmode: String,
+    /// Input files
+    pub inputs: Vec<InputFile>,
+    /// Preprocessing pipeline, that may be replayed with --pipeline
+    pub preprocessing: Vec<Step>,
+    /// Generated output products
+    pub outputs: Vec<PathBuf>,
+    /// Session start time (UTC)
+    pub start_time: Option<Epoch>,
+    /// Session duration, in seconds: loading of all input files,
+    /// then processing of this station
+    pub duration_s: f64,
+    /// True if the session succeeded
+    pub success: bool,
+    /// Process exit code
+    pub exit_code: u8,
+    /// Warnings emitted during this session
+    pub warnings: Vec<String>,
+    /// Errors emitted during this session
+    pub errors: Vec<String>,
+}
+
+/// [RunSummary] being built, shared by all operations of a session
+pub struct Summary {
+    /// Session start
+    start: Instant,
+    /// Summary content
+    inner: RefCell<RunSummary>,
+}
+
+impl Summary {
+    /// Starts a new [Summary], once input files have been loaded:
+    /// `start_time` is the session start and `loading` the time it took to load the input files.
+    pub fn new(
+        session: &str,
+        opmode: &str,
+        inputs: Vec<InputFile>,
+        preprocessing: Vec<Step>,
+        start_time: Option<Epoch>,
+        loading: Duration,
+    ) -> Self {
+        Self {
+            start: Instant::now()
+                .checked_sub(loading)
+                .unwrap_or_else(Instant::now),
+            inner: RefCell::new(RunSummary {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                session: session.to_string(),
+                opmode: opmode.to_string(),
+                inputs,
+                preprocessing,
+                start_time,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Declares a new output product
+    pub fn output<P: AsRef<Path>>(&self, path: P) {
+        self.inner
+            .borrow_mut()
+            .outputs
+            .push(path.as_ref().to_path_buf());
+    }
+
+    /// Returns the path of all input files
+    pub fn inputs(&self) -> Vec<PathBuf> {
+        self.inner
+            .borrow()
+            .inputs
+            .iter()
+            .map(|input| input.path.clone())
+            .collect()
+    }
+
+    /// Completes this [Summary] with the session result and the
+    /// warnings and errors that were emitted, then writes it as run.json
+    /// in the workspace.
+    pub fn write(
+        &self,
+        workspace: &Path,
+        result: &Result<(), Error>,
+        logs: Vec<(Level, String)>,
+    ) -> std::io::Result<()> {
+        let mut summary = self.inner.borrow_mut();
+
+        summary.duration_s = self.start.elapsed().as_secs_f64();
+        summary.success = result.is_ok();
+        summary.exit_code = match result {
+            Ok(_) => 0,
+            Err(e) => e.exit_code(),
+        };
+
+        for (level, message) in logs {
+            if level == Level::Error {
+                summary.errors.push(message);
+            } else {
+                summary.warnings.push(message);
+            }
+        }
+
+        if let Err(e) = result {
+            summary.errors.push(e.to_string());
+        }
+
+        let path = workspace.join("run.json");
+        let fd = File::create(&path)?;
+        serde_json::to_writer_pretty(fd, &*summary)?;
+
+        info!("run summary \"{}\" has been generated", path.display());
+        Ok(())
+    }
+}